@@ -1,9 +1,16 @@
+use std::any::Any;
+
 use sdl2::event::Event;
 
 use crate::egui_scene::EguiWidget;
+use crate::gamepad::GamepadInfo;
+use crate::gesture::Gesture;
+use crate::input_map::InputBinding;
 use crate::layout_manager::Layout;
 use crate::scene::Scene;
+use crate::scene::SceneFlags;
 use crate::scene::SceneID;
+use crate::transition::Transition;
 use bitflags::bitflags;
 
 // TODO: do better : avoid all this boilerplate !!!
@@ -26,6 +33,16 @@ bitflags! {
     const ButtonPressed = 1 << 4;
     const SceneMsg      = 1 << 5;
     const Misc          = 1 << 6;
+    const SdlTextInput  = 1 << 7;
+    const SdlMouseWheel = 1 << 8;
+    const Pointer       = 1 << 9; // PointerEnter, PointerLeave, PointerMove
+    const Focus         = 1 << 10; // FocusIn, FocusOut
+    const Input         = 1 << 11; // Action::Input, cf. the InputMap
+    const SdlController = 1 << 12; // Gamepad buttons and axes (GamepadConnected / GamepadDisconnected go to every scene)
+    const SdlTouch      = 1 << 13; // FingerDown, FingerMotion, FingerUp, MultiGesture
+    const Gesture       = 1 << 14; // Action::Gesture
+    const Animation     = 1 << 15; // AnimationFinished
+    const Preload       = 1 << 16; // PreloadProgress, PreloadDone
   }
 }
 
@@ -33,6 +50,37 @@ pub enum Action {
   // -- Raw SDL2 events (engine to user space)
   SdlEvent(sdl2::event::Event),
 
+  // -- Hovering (engine to user space), for the scenes subscribed to EventKind::Pointer.
+  // Only the topmost scene whose click zone is under the pointer is hovered.
+  PointerEnter,
+  PointerLeave,
+  PointerMove { x: i32, y: i32 },
+
+  // -- Drag and drop. A scene which got a mouse press (and thus captured the pointer) pushes 
+  // StartDrag, e.g. when the pointer moved far enough. The drop targets are the scenes under 
+  // the pointer whose accepts_drop() returns true.
+  StartDrag { payload: DragPayload }, // (user to engine)
+  DragEnter { source: SceneID }, // (engine to the drop targets)
+  DragOver { source: SceneID, x: i32, y: i32 },
+  DragLeave { source: SceneID },
+  Drop { source: SceneID, payload: DragPayload, x: i32, y: i32 }, // Return true to accept it
+  DragEnd { target: Option<SceneID>, accepted: bool }, // (engine to the dragged scene)
+
+  // -- Touch gestures (engine to the scene under the gesture)
+  Gesture(Gesture),
+
+  // -- Gamepads hot-plug (engine to user space)
+  GamepadConnected { pad: GamepadInfo },
+  GamepadDisconnected { id: u32 },
+
+  // -- Logical inputs (engine to user space) : a physical input bound to that name in the 
+  // InputMap was pressed or released. Given to the focused scene first.
+  Input { name: String, pressed: bool },
+
+  // -- Keyboard focus (engine to the scene gaining / losing it), see Scene::is_focusable
+  FocusIn,
+  FocusOut,
+
   // -- Engine-level commands
   // The engine itself interprets these, e.g., to push or pop scenes, start music, etc.
   // For an SDL event : propagate the events to the scenes below.
@@ -52,10 +100,62 @@ pub enum Action {
     size: String, // e.g. "small" "medium" "big" or directly a number like "35"
     text: String // + TODO max_width Option<u32> 
   },
+  // Replace the current scene (and its subtree) by a new one, on the same layer and with the same parent.
+  // Like Action::Scene, the bus gives back the ID of the new scene.
+  ReplaceScene {
+    with: Box<dyn Scene>,
+    transition: Transition
+  },
+  // Navigation stack ("go back" semantics) hosted by the pushing scene, or by the host of the 
+  // navigation stack the pushing scene belongs to. The previous screen is hidden, paused and 
+  // input-disabled until it is popped back. The Escape key and the gamepad B button pop too.
+  NavPush {
+    scene: Box<dyn Scene>,
+    layer: usize
+  },
+  NavReplace { // The top screen is closed and replaced
+    scene: Box<dyn Scene>,
+    layer: usize
+  },
+  NavPop,
+  NavPopToRoot,
   CloseCurrentScene, 
   CloseScene {
     target_id: SceneID,
   },
+  // Hide / pause / disable the input of a scene and its whole subtree, without removing it.
+  // target_id None means the scene pushing the action.
+  SetSceneFlags {
+    target_id: Option<SceneID>,
+    flags: SceneFlags,
+    enabled: bool // false to clear the flags
+  },
+  // Give the keyboard focus to a focusable scene. target_id None means the scene pushing the action.
+  SetFocus {
+    target_id: Option<SceneID>
+  },
+  // Input bindings : replace the bindings of a logical input (e.g. from an options screen)
+  RebindInput {
+    name: String,
+    binding: InputBinding
+  },
+  // Save the current bindings under the userdata path, they are loaded at the next start
+  SaveInputMap,
+  // Gamepad vibration. pad None means every connected pad. Intensities from 0 to 0xFFFF.
+  Rumble {
+    pad: Option<u32>,
+    low: u16,
+    high: u16,
+    duration_ms: u32
+  },
+  // Load the textures of these sheets (the "file" of spritedesc.json, all of them if empty) in the 
+  // background, e.g. from a loading screen. The scene pushing it gets PreloadProgress every time
+  // some textures are ready, then PreloadDone (even if nothing had to be loaded).
+  Preload {
+    sheets: Vec<String>
+  },
+  PreloadProgress { loaded: usize, total: usize }, // (engine to user space)
+  PreloadDone,
   // Simplistic music commands for now (TODO?)
   StartMusic {
     track: String, // e.g. "intro.mp3"
@@ -70,8 +170,18 @@ pub enum Action {
 
   RequestLayout(Layout),
 
+  // The window is being closed (or the OS asked the game to quit).
+  // Sent to every scene, from the top to the bottom : returning true from handle_action vetoes it.
+  // (e.g. to prompt an "unsaved changes?" dialog, which would push Action::Quit later)
+  QuitRequested,
+  // Exit the main loop cleanly
+  Quit,
+
   ButtonPressed,
 
+  // A one-shot clip of an AnimationPlayer ended (pushed from the update() of the animated scene)
+  AnimationFinished { clip: String },
+
   // -- Scene-to-scene messages
   // The engine will find the target scene and call `handle_event(Action::SceneMsg { ... })` on it.
   SceneMsg {
@@ -85,16 +195,49 @@ impl Action {
     match self {
       Action::SdlEvent(evt) => match evt {
         Event::MouseButtonDown {..} | Event::MouseButtonUp {..} => EventKind::SdlMouseClick,
+        Event::MouseMotion {..} => EventKind::SdlMouseHover,
+        Event::MouseWheel {..} => EventKind::SdlMouseWheel,
         Event::KeyDown {..} | Event::KeyUp {..} => EventKind::SdlKeyboard,
+        Event::TextInput {..} | Event::TextEditing {..} => EventKind::SdlTextInput,
+        Event::ControllerButtonDown {..} | Event::ControllerButtonUp {..} 
+        | Event::ControllerAxisMotion {..} => EventKind::SdlController,
+        Event::FingerDown {..} | Event::FingerMotion {..} | Event::FingerUp {..} 
+        | Event::MultiGesture {..} => EventKind::SdlTouch,
         _ => EventKind::SdlMisc
       },
+      Action::PointerEnter | Action::PointerLeave | Action::PointerMove { .. } 
+      | Action::DragEnter { .. } | Action::DragOver { .. } | Action::DragLeave { .. } 
+      | Action::Drop { .. } | Action::DragEnd { .. } => EventKind::Pointer,
+      Action::FocusIn | Action::FocusOut => EventKind::Focus,
+      Action::Input { .. } => EventKind::Input,
+      Action::Gesture(_) => EventKind::Gesture,
+      Action::GamepadConnected { .. } | Action::GamepadDisconnected { .. } => EventKind::SdlController,
       Action::ButtonPressed { .. } => EventKind::ButtonPressed,
+      Action::AnimationFinished { .. } => EventKind::Animation,
+      Action::PreloadProgress { .. } | Action::PreloadDone => EventKind::Preload,
       Action::SceneMsg { .. } => EventKind::SceneMsg,
       _ => EventKind::NotAnEvent
     }
   }
 }
 
+// What is being dragged : a kind (e.g. "card", "item") to easily filter the drop targets, 
+// and any data
+pub struct DragPayload {
+  pub kind: String,
+  pub data: Box<dyn Any>,
+}
+
+impl DragPayload {
+  pub fn new<T: Any>(kind: &str, data: T) -> Self {
+    Self { kind: kind.to_owned(), data: Box::new(data) }
+  }
+
+  pub fn data<T: Any>(&self) -> Option<&T> {
+    self.data.downcast_ref::<T>()
+  }
+}
+
 // TODO 
 // This is a pure sample 
 // Optional sub-enum for more structured "scene-to-scene" messages
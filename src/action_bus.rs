@@ -1,6 +1,6 @@
 use std::{cell::Cell, rc::Rc};
 use tracing::debug;
-use crate::{action::Action, hg::HamID, scene::SceneID};
+use crate::{action::Action, hg::HamID, scene::SceneID, sprite::{SharedCatalog, SpriteHandle}, animation::AnimationClip};
 
 const TRABUS: &str = "hg::bus";
 
@@ -23,6 +23,7 @@ pub struct ActionBus {
   cur_processed_scene: SceneID, // To set the right source scene ID when actions are pushed
   pub(crate) next_sprite_id: Rc<Cell<usize>>, // Also used to sync with the sprite store as back_id
   sprite_id_offset: usize, // in case there are more sprites 
+  sprite_catalog: SharedCatalog, // The names of the sprite store
 
   actions_priv: Vec<ActionPriv>,
 
@@ -37,11 +38,12 @@ pub struct ActionBus {
 
 impl ActionBus {
   // Constructor: Expose only within the crate
-  pub(crate) fn new(shared_spritestore_len: Rc<Cell<usize>>) -> Self {
+  pub(crate) fn new(shared_spritestore_len: Rc<Cell<usize>>, sprite_catalog: SharedCatalog) -> Self {
     ActionBus {
       cur_processed_scene: 0,
       next_sprite_id: shared_spritestore_len,
       sprite_id_offset: 0, 
+      sprite_catalog,
 
       actions_priv: Vec::new(),
       prioritary: Vec::new(),
@@ -50,9 +52,11 @@ impl ActionBus {
   }
 
   // Sync with the other data structures ... 
+  // The future scene ID never goes backwards : IDs may already have been given to scenes
+  // whose creation action is still waiting in the bus.
   pub fn prepare(&mut self, cur_processed_scene: SceneID, next_scene_id: SceneID) {
     self.cur_processed_scene = cur_processed_scene;
-    self.future_scene_id = next_scene_id;
+    self.future_scene_id = self.future_scene_id.max(next_scene_id);
   }
 
   // Allow pushing actions, make it public
@@ -63,7 +67,8 @@ impl ActionBus {
     let mut ret: Option<HamID> = None;
 
     match action {
-      Action::Scene {..} | Action::ImmediateUI {..} => {
+      Action::Scene {..} | Action::ImmediateUI {..} | Action::ReplaceScene {..} 
+      | Action::NavPush {..} | Action::NavReplace {..} => {
         debug!(target="hg::bus", "User asked for new scene - provided id=<{}>", self.future_scene_id);
         ret = Some(HamID::SceneID(self.future_scene_id));
         self.future_scene_id += 1;
@@ -82,6 +87,16 @@ impl ActionBus {
     ret  
   }
 
+  // Look up a sprite of spritedesc.json by name (e.g. in Scene::init, to keep the handle)
+  pub fn sprite(&self, name: &str) -> Option<SpriteHandle> {
+    self.sprite_catalog.borrow().sprite(name)
+  }
+
+  // An animation clip of spritedesc.json, e.g. for AnimationPlayer::new in Scene::init
+  pub fn animation(&self, name: &str) -> Option<Rc<AnimationClip>> {
+    self.sprite_catalog.borrow().clip(name)
+  }
+
   // Method to consume the events (used internally by the engine)
   pub(crate) fn take_all(&mut self) -> Vec<ActionPriv> {
     std::mem::take(&mut self.actions_priv)
@@ -0,0 +1,200 @@
+use std::rc::Rc;
+
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::{action::Action, action_bus::ActionBus, sprite::{DrawParams, SpriteHandle}, Renderer};
+
+const TRAANIM: &str = "hg::anim";
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationMode {
+  #[default]
+  Loop,
+  PingPong, // 0 1 2 1 0 1 ...
+  Once, // Stops on the last frame, then Action::AnimationFinished
+}
+
+// A clip of spritedesc.json (the "animations" of a sheet). Get it with ActionBus::animation
+// or SpriteStore::clip.
+#[derive(Debug)]
+pub struct AnimationClip {
+  name: String,
+  frames: Vec<SpriteHandle>,
+  durations: Vec<f32>, // Seconds, per frame
+  mode: AnimationMode,
+}
+
+impl AnimationClip {
+  pub fn new(name: &str, frames: Vec<SpriteHandle>, durations_ms: Vec<u32>, mode: AnimationMode) -> Self {
+    assert!(!frames.is_empty() && frames.len() == durations_ms.len(), "AnimationClip {}: bad frames", name);
+    // At least 1ms, or update() would never end
+    let durations = durations_ms.iter().map(|d| (*d).max(1) as f32 / 1000.0).collect();
+    Self { name: name.to_owned(), frames, durations, mode }
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn frames(&self) -> &[SpriteHandle] {
+    &self.frames
+  }
+
+  pub fn mode(&self) -> AnimationMode {
+    self.mode
+  }
+
+  // One pass over the frames, in seconds
+  pub fn duration(&self) -> f32 {
+    self.durations.iter().sum()
+  }
+}
+
+// Plays a clip : call update() from Scene::update and render() from Scene::render
+pub struct AnimationPlayer {
+  clip: Rc<AnimationClip>,
+  frame: usize,
+  elapsed: f32, // In the current frame
+  forward: bool, // PingPong
+  finished: bool,
+  pub speed: f32, // 1.0 : as declared. 0.0 : paused
+}
+
+impl AnimationPlayer {
+  pub fn new(clip: Rc<AnimationClip>) -> Self {
+    Self { clip, frame: 0, elapsed: 0.0, forward: true, finished: false, speed: 1.0 }
+  }
+
+  // Switches to another clip (from its start). Does nothing if it is already playing.
+  pub fn play(&mut self, clip: Rc<AnimationClip>) {
+    if !Rc::ptr_eq(&self.clip, &clip) {
+      self.clip = clip;
+      self.restart();
+    }
+  }
+
+  pub fn restart(&mut self) {
+    self.frame = 0;
+    self.elapsed = 0.0;
+    self.forward = true;
+    self.finished = false;
+  }
+
+  pub fn clip(&self) -> &AnimationClip {
+    &self.clip
+  }
+
+  pub fn frame(&self) -> SpriteHandle {
+    self.clip.frames[self.frame]
+  }
+
+  pub fn is_finished(&self) -> bool {
+    self.finished
+  }
+
+  // Returns true when a Once clip just ended (Action::AnimationFinished is pushed too)
+  pub fn update(&mut self, delta_time: f32, action_bus: &mut ActionBus) -> bool {
+    if self.finished {
+      return false;
+    }
+    self.elapsed += delta_time * self.speed;
+    while self.elapsed >= self.clip.durations[self.frame] {
+      self.elapsed -= self.clip.durations[self.frame];
+      if self.advance() {
+        debug!(target: TRAANIM, "Animation {} finished", self.clip.name);
+        action_bus.push(Action::AnimationFinished { clip: self.clip.name.clone() });
+        return true;
+      }
+    }
+    false
+  }
+
+  pub fn render(&self, renderer: &mut Renderer, x: i32, y: i32, params: impl Into<DrawParams>) {
+    renderer.render_sprite(self.frame(), x, y, params);
+  }
+
+  // Next frame. Returns true if a Once clip is over.
+  fn advance(&mut self) -> bool {
+    let last = self.clip.frames.len() - 1;
+    match self.clip.mode {
+      AnimationMode::Loop => {
+        self.frame = if self.frame == last { 0 } else { self.frame + 1 };
+      },
+      AnimationMode::PingPong => {
+        if last == 0 {
+          return false;
+        }
+        if self.forward && self.frame == last {
+          self.forward = false;
+        }
+        else if !self.forward && self.frame == 0 {
+          self.forward = true;
+        }
+        self.frame = if self.forward { self.frame + 1 } else { self.frame - 1 };
+      },
+      AnimationMode::Once => {
+        if self.frame == last {
+          self.elapsed = 0.0;
+          self.finished = true;
+          return true;
+        }
+        self.frame += 1;
+      }
+    }
+    false
+  }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use std::cell::Cell;
+
+  use super::*;
+
+  fn clip(mode: AnimationMode) -> Rc<AnimationClip> {
+    Rc::new(AnimationClip::new("walk", vec![SpriteHandle(10), SpriteHandle(11), SpriteHandle(12)], vec![100, 100, 200], mode))
+  }
+
+  fn frames(player: &mut AnimationPlayer, bus: &mut ActionBus, steps: usize) -> Vec<usize> {
+    (0..steps).map(|_| { player.update(0.1, bus); player.frame().id() }).collect()
+  }
+
+  #[test]
+  fn test_loop_and_ping_pong() {
+    let mut bus = ActionBus::new(Rc::new(Cell::new(0)), Default::default());
+    let mut player = AnimationPlayer::new(clip(AnimationMode::Loop));
+    assert_eq!(player.frame(), SpriteHandle(10));
+    assert_eq!(frames(&mut player, &mut bus, 6), vec![11, 12, 12, 10, 11, 12]);
+
+    player.play(clip(AnimationMode::PingPong));
+    assert_eq!(frames(&mut player, &mut bus, 7), vec![11, 12, 12, 11, 10, 11, 12]);
+
+    // A big step skips frames
+    player.restart();
+    player.update(0.45, &mut bus);
+    assert_eq!(player.frame(), SpriteHandle(11));
+    assert!(bus.take_all().is_empty());
+  }
+
+  #[test]
+  fn test_once() {
+    let mut bus = ActionBus::new(Rc::new(Cell::new(0)), Default::default());
+    let mut player = AnimationPlayer::new(clip(AnimationMode::Once));
+    player.speed = 2.0;
+    assert!(!player.update(0.1, &mut bus));
+    assert_eq!(player.frame(), SpriteHandle(12));
+    assert!(player.update(0.1, &mut bus));
+    assert!(player.is_finished());
+    assert_eq!(player.frame(), SpriteHandle(12)); // Stays on the last frame
+    assert!(!player.update(1.0, &mut bus)); // Only once
+
+    let actions = bus.take_all();
+    assert_eq!(actions.len(), 1);
+    assert!(matches!(&actions[0].action, Action::AnimationFinished { clip } if clip == "walk"));
+  }
+}
@@ -0,0 +1,228 @@
+// Imports the atlases exported by TexturePacker (JSON hash or array) and Aseprite (JSON) as
+// spritedesc.json sheets, e.g. { "import": "hero.json", "format": "aseprite" } in spritedesc.json
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use serde::{de::{MapAccess, Visitor}, Deserialize, Deserializer};
+use tracing::debug;
+
+use crate::{animation::AnimationMode, sprite::{ClipJsonRep, SpriteJsonRep, SpriteSheetJsonRep, Trim}, vfs};
+
+const TRAATLAS: &str = "hg::atlas";
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AtlasFormat {
+  TexturePacker, // + its "animations" (Pixi style)
+  Aseprite, // + the frame durations and tags
+}
+
+#[derive(Deserialize, Debug)]
+struct AtlasJson {
+  frames: AtlasFrames,
+  meta: AtlasMeta,
+  animations: Option<BTreeMap<String, Vec<String>>>, // TexturePacker (Pixi)
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum AtlasFrames {
+  Array(Vec<NamedFrame>),
+  Hash(#[serde(deserialize_with = "ordered_map")] Vec<(String, AtlasFrame)>), // The tags need the order
+}
+
+#[derive(Deserialize, Debug)]
+struct NamedFrame {
+  filename: String,
+  #[serde(flatten)]
+  frame: AtlasFrame,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AtlasFrame {
+  frame: RectRep,
+  #[serde(default)]
+  rotated: bool,
+  #[serde(default)]
+  trimmed: bool,
+  sprite_source_size: Option<RectRep>,
+  source_size: Option<SizeRep>,
+  duration: Option<u32>, // Aseprite, in ms
+}
+
+#[derive(Deserialize, Debug)]
+struct RectRep { x: i32, y: i32, w: u32, h: u32 }
+
+#[derive(Deserialize, Debug)]
+struct SizeRep { w: u32, h: u32 }
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AtlasMeta {
+  app: Option<String>,
+  image: String, // Relative to the atlas file
+  #[serde(default)]
+  frame_tags: Vec<FrameTag>,
+}
+
+#[derive(Deserialize, Debug)]
+struct FrameTag {
+  name: String,
+  from: usize,
+  to: usize,
+  #[serde(default)]
+  direction: String, // forward, reverse, pingpong, pingpong_reverse
+  repeat: Option<serde_json::Value>, // "1" (a string) for a one-shot tag
+}
+
+// A JSON object, in the file order (serde_json sorts them without its preserve_order feature)
+fn ordered_map<'de, D: Deserializer<'de>>(de: D) -> Result<Vec<(String, AtlasFrame)>, D::Error> {
+  struct OrderedVisitor;
+  impl<'de> Visitor<'de> for OrderedVisitor {
+    type Value = Vec<(String, AtlasFrame)>;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      f.write_str("a map of frames")
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+      let mut frames = Vec::new();
+      while let Some(entry) = map.next_entry()? {
+        frames.push(entry);
+      }
+      Ok(frames)
+    }
+  }
+  de.deserialize_map(OrderedVisitor)
+}
+
+// import : relative to the img folder. format : None to guess it.
+pub(crate) fn import_atlas(img_dir: &Path, import: &str, format: Option<AtlasFormat>) -> Result<SpriteSheetJsonRep, String> {
+  let text = vfs::read(&img_dir.join(import)).map_err(|e| format!("{}: {}", import, e))?;
+  let text = String::from_utf8(text).map_err(|e| format!("{}: {}", import, e))?;
+  parse_atlas(import, &text, format)
+}
+
+pub(crate) fn parse_atlas(import: &str, text: &str, format: Option<AtlasFormat>) -> Result<SpriteSheetJsonRep, String> {
+  let atlas: AtlasJson = serde_json::from_str(text).map_err(|e| format!("{}: {}", import, e))?;
+  let format = format.unwrap_or_else(|| guess_format(import, &atlas.meta));
+  debug!(target: TRAATLAS, "Importing {} as {:?}", import, format);
+
+  let frames = match atlas.frames {
+    AtlasFrames::Array(frames) => frames.into_iter().map(|f| (f.filename, f.frame)).collect(),
+    AtlasFrames::Hash(frames) => frames
+  };
+
+  let mut animations = Vec::new();
+  match format {
+    AtlasFormat::Aseprite => {
+      for tag in &atlas.meta.frame_tags {
+        if tag.from > tag.to || tag.to >= frames.len() {
+          return Err(format!("{}: tag {} out of the frames", import, tag.name));
+        }
+        let mut range: Vec<&(String, AtlasFrame)> = frames[tag.from..=tag.to].iter().collect();
+        if tag.direction.ends_with("reverse") {
+          range.reverse();
+        }
+        let once = tag.repeat.as_ref()
+          .and_then(|r| r.as_u64().or_else(|| r.as_str().and_then(|s| s.parse().ok()))) == Some(1);
+        let mode = if tag.direction.starts_with("pingpong") { AnimationMode::PingPong }
+          else if once { AnimationMode::Once } else { AnimationMode::Loop };
+        animations.push(ClipJsonRep {
+          name: tag.name.clone(),
+          frames: range.iter().map(|(name, _)| name.clone()).collect(),
+          frame_ms: 100,
+          durations_ms: Some(range.iter().map(|(_, f)| f.duration.unwrap_or(100)).collect()),
+          mode
+        });
+      }
+    },
+    AtlasFormat::TexturePacker => {
+      for (name, frames) in atlas.animations.unwrap_or_default() {
+        animations.push(ClipJsonRep { name, frames, frame_ms: 100, durations_ms: None, mode: AnimationMode::Loop });
+      }
+    }
+  }
+
+  let sprites = frames.into_iter().map(|(name, f)| {
+    // Where the trimmed frame sits in the original image
+    let trim = match (f.trimmed, f.sprite_source_size, f.source_size) {
+      (true, Some(sss), Some(size)) => Some(Trim { x: sss.x, y: sss.y, source_w: size.w, source_h: size.h }),
+      _ => None
+    };
+    SpriteJsonRep {
+      name, x: f.frame.x, y: f.frame.y, w: f.frame.w, h: f.frame.h, make_alone: None, nine_slice: None,
+      rotated: f.rotated, trim
+    }
+  }).collect();
+
+  // The image is next to the atlas
+  let file = Path::new(import).parent().unwrap_or(Path::new("")).join(&atlas.meta.image);
+  Ok(SpriteSheetJsonRep { file: file.to_string_lossy().into_owned(), sprites, animations, packed: false })
+}
+
+// hero.aseprite.json, hero.ase.json, or the app which exported it
+fn guess_format(import: &str, meta: &AtlasMeta) -> AtlasFormat {
+  let aseprite = import.ends_with(".aseprite.json") || import.ends_with(".ase.json")
+    || meta.app.as_ref().is_some_and(|app| app.contains("aseprite"));
+  if aseprite { AtlasFormat::Aseprite } else { AtlasFormat::TexturePacker }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_texture_packer() {
+    let hash = r#"{
+      "frames": {
+        "walk_2.png": { "frame": {"x":40,"y":0,"w":20,"h":30}, "rotated": true, "trimmed": false,
+          "spriteSourceSize": {"x":0,"y":0,"w":20,"h":30}, "sourceSize": {"w":20,"h":30} },
+        "walk_1.png": { "frame": {"x":0,"y":0,"w":28,"h":30}, "rotated": false, "trimmed": true,
+          "spriteSourceSize": {"x":2,"y":1,"w":28,"h":30}, "sourceSize": {"w":32,"h":32} }
+      },
+      "animations": { "walk": ["walk_1.png", "walk_2.png"] },
+      "meta": { "app": "https://www.codeandweb.com/texturepacker", "image": "hero.png" }
+    }"#;
+    let sheet = parse_atlas("chars/hero.json", hash, None).unwrap();
+    assert_eq!(sheet.file, Path::new("chars").join("hero.png").to_string_lossy());
+    assert_eq!(sheet.sprites.len(), 2);
+    assert_eq!(sheet.sprites[0].name, "walk_2.png"); // File order
+    assert!(sheet.sprites[0].rotated && sheet.sprites[0].trim.is_none());
+    assert_eq!(sheet.sprites[1].trim, Some(Trim { x: 2, y: 1, source_w: 32, source_h: 32 }));
+    assert_eq!(sheet.animations.len(), 1);
+    assert_eq!(sheet.animations[0].frames, vec!["walk_1.png", "walk_2.png"]);
+
+    let array = r#"{
+      "frames": [ { "filename": "a", "frame": {"x":0,"y":0,"w":8,"h":8} } ],
+      "meta": { "image": "items.png" }
+    }"#;
+    let sheet = parse_atlas("items.json", array, Some(AtlasFormat::TexturePacker)).unwrap();
+    assert_eq!((sheet.file.as_str(), sheet.sprites[0].w), ("items.png", 8));
+  }
+
+  #[test]
+  fn test_aseprite() {
+    let json = r#"{
+      "frames": {
+        "hero 0.aseprite": { "frame": {"x":0,"y":0,"w":16,"h":16}, "duration": 100 },
+        "hero 1.aseprite": { "frame": {"x":16,"y":0,"w":16,"h":16}, "duration": 150 },
+        "hero 2.aseprite": { "frame": {"x":32,"y":0,"w":16,"h":16}, "duration": 200 }
+      },
+      "meta": { "app": "https://www.aseprite.org/", "image": "hero.png", "frameTags": [
+        { "name": "idle", "from": 0, "to": 1, "direction": "pingpong" },
+        { "name": "die", "from": 1, "to": 2, "direction": "reverse", "repeat": "1" }
+      ] }
+    }"#;
+    let sheet = parse_atlas("hero.json", json, None).unwrap();
+    let (idle, die) = (&sheet.animations[0], &sheet.animations[1]);
+    assert_eq!(idle.mode, AnimationMode::PingPong);
+    assert_eq!(idle.durations_ms, Some(vec![100, 150]));
+    assert_eq!(die.frames, vec!["hero 2.aseprite", "hero 1.aseprite"]);
+    assert_eq!(die.mode, AnimationMode::Once);
+
+    let bad = json.replace("\"to\": 2", "\"to\": 3");
+    assert!(parse_atlas("hero.json", &bad, None).is_err());
+  }
+}
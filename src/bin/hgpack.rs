@@ -0,0 +1,42 @@
+// Packs the install folder (img, conf, font, music, sfx...) into a single archive, read by the engine
+// instead of the loose files when it's <install path>/assets.hgpack
+//   hgpack <install folder> <archive> [--store]
+//   hgpack --list <archive>
+use std::{path::Path, process::ExitCode};
+
+use hamgraph::vfs::{self, Archive};
+
+const USAGE: &str = "usage: hgpack <install folder> <archive> [--store]\n       hgpack --list <archive>";
+
+fn main() -> ExitCode {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let res = match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+    ["--list", archive] => list(Path::new(archive)),
+    [dir, archive] => pack(Path::new(dir), Path::new(archive), true),
+    [dir, archive, "--store"] => pack(Path::new(dir), Path::new(archive), false),
+    _ => Err(USAGE.to_owned())
+  };
+  match res {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("{}", e);
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn pack(dir: &Path, archive: &Path, compress: bool) -> Result<(), String> {
+  let count = vfs::pack_dir(dir, archive, compress && cfg!(feature = "compression"))
+    .map_err(|e| format!("Cannot pack {:?}: {}", dir, e))?;
+  println!("{} files packed into {:?}", count, archive);
+  Ok(())
+}
+
+fn list(path: &Path) -> Result<(), String> {
+  let archive = Archive::open(path).map_err(|e| format!("Cannot open {:?}: {}", path, e))?;
+  for name in archive.names() {
+    let (stored, original) = archive.sizes(name).unwrap_or_default();
+    println!("{:>10} {:>10}  {}", original, stored, name);
+  }
+  Ok(())
+}
@@ -1,19 +1,43 @@
 // Temporary placed here. To be part of HamUI. 
-use crate::{action::{Action, EventKind}, action_bus::ActionBus, Renderer, layout_manager::Layout, scene::{Scene, SceneID}, text_scene::TextScene, utils::is_point_in_rect};
-use sdl2::{event::Event, mouse::MouseButton, pixels::Color, rect::Rect};
+use crate::{action::{Action, EventKind}, action_bus::ActionBus, Renderer, layout_manager::Layout, scene::{Scene, SceneID}, sprite::{DrawParams, SpriteHandle}, text_scene::TextScene};
+use sdl2::{controller::Button, event::Event, keyboard::Keycode, mouse::MouseButton, pixels::Color, rect::Rect};
 use tracing::debug;
 
+// Nine-slice sprites drawn instead of the plain color. A missing state uses the normal one, tinted.
+#[derive(Clone, Copy, Debug)]
+pub struct ButtonSkin {
+  pub normal: SpriteHandle,
+  pub hovered: Option<SpriteHandle>,
+  pub pressed: Option<SpriteHandle>,
+}
+
 pub struct ButtonScene {
   pos: Option<Rect>,
   lil_name: String,
   pressed: bool, 
+  hovered: bool,
   color_tmp: Color, // temporary because there will be a better style system obviously
+  skin: Option<ButtonSkin>,
   layout: Layout
 }
 
 impl ButtonScene {
   pub fn new(lil_name: &str, color_tmp: Color, layout: Layout) -> Self { 
-    Self{pos: None, lil_name: lil_name.to_string(), pressed: false, color_tmp, layout}
+    Self{pos: None, lil_name: lil_name.to_string(), pressed: false, hovered: false, color_tmp, skin: None, layout}
+  }
+
+  pub fn with_skin(mut self, skin: ButtonSkin) -> Self {
+    self.skin = Some(skin);
+    self
+  }
+
+  fn render_skin(&self, renderer: &mut Renderer, skin: &ButtonSkin, rect: Rect) {
+    let (sprite, tint) = match (self.pressed, self.hovered) {
+      (true, _) => skin.pressed.map_or((skin.normal, Some(Color::RGB(170, 170, 200))), |s| (s, None)),
+      (false, true) => skin.hovered.map_or((skin.normal, Some(Color::RGB(225, 225, 225))), |s| (s, None)),
+      _ => (skin.normal, None)
+    };
+    renderer.render_nine_slice(sprite, rect, DrawParams { tint, ..Default::default() });
   }
 }
 
@@ -25,16 +49,26 @@ impl Scene for ButtonScene {
   }
 
   fn render(&self, renderer: &mut Renderer) {
-    if self.pos.is_none() { return; }
+    let Some(pos) = self.pos else { return; };
+    if let Some(skin) = &self.skin {
+      self.render_skin(renderer, skin, pos);
+      return;
+    }
     if self.pressed {
       renderer.canvas.set_draw_color(Color::RGB(0, 100, 255));
     }
+    else if self.hovered {
+      let c = self.color_tmp;
+      renderer.canvas.set_draw_color(Color::RGB(c.r.saturating_add(40), c.g.saturating_add(40), c.b.saturating_add(40)));
+    }
     else {
       renderer.canvas.set_draw_color(self.color_tmp);
     }
     renderer.canvas.fill_rect(self.pos).map_err(|e| e.to_string()).unwrap(); 
   }
 
+  fn is_focusable(&self) -> bool { true }
+
   fn left_click_zone(&self) -> Option<Rect> {
     self.pos
   }
@@ -55,14 +89,23 @@ impl Scene for ButtonScene {
             }
             debug!(target: "hgui::button", "Button up!");
             self.pressed = false;
-            if is_point_in_rect(&self.pos.unwrap(), *x, *y) {
+            if self.hit_test(*x, *y) {
               action_bus.push(Action::ButtonPressed);
             } // Else the button is still left unpressed but no event! 
             true
           },
+          // Only received when focused
+          Event::KeyDown { keycode: Some(Keycode::Return | Keycode::KpEnter | Keycode::Space), repeat: false, .. } 
+          | Event::ControllerButtonDown { button: Button::A, .. } => {
+            debug!(target: "hgui::button", "{} activated from the keyboard / gamepad", &self.lil_name);
+            action_bus.push(Action::ButtonPressed);
+            true
+          },
           _ => { false }
         }
       }, 
+      Action::PointerEnter => { self.hovered = true; true },
+      Action::PointerLeave => { self.hovered = false; true },
       _ => { false } // unexpected given the subscriptions! ... 
     }
   }
@@ -73,6 +116,6 @@ impl Scene for ButtonScene {
   }
 
   fn subscriptions(&self) -> EventKind {
-    EventKind::SdlMouseClick
+    EventKind::SdlMouseClick | EventKind::Pointer
   }
 }
@@ -0,0 +1,182 @@
+use sdl2::{pixels::Color, rect::Rect};
+use tracing::debug;
+
+use crate::{action::Action, action_bus::ActionBus, layout_manager::LayoutManager, scene::{SceneID, SceneStack}, Renderer};
+
+const TRAFOCUS: &str = "hg::focus";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum FocusMove {
+  Next, // Tab
+  Previous, // Shift+Tab
+  Up, // D-pad : the nearest focusable scene in that direction
+  Down,
+  Left,
+  Right,
+}
+
+// Keyboard focus : at most one focused scene, which gets the keyboard, text and gamepad button events first.
+pub(crate) struct FocusManager {
+  focused: Option<SceneID>,
+  visible: bool, // The indicator is only drawn when the focus was moved with the keyboard / gamepad
+}
+
+impl FocusManager {
+  pub(crate) fn new() -> Self {
+    Self { focused: None, visible: false }
+  }
+
+  pub(crate) fn focused(&self) -> Option<SceneID> {
+    self.focused
+  }
+
+  pub(crate) fn set(&mut self, id: Option<SceneID>, visible: bool, scene_stack: &mut SceneStack, action_bus: &mut ActionBus) {
+    self.visible = visible;
+    if id == self.focused {
+      return;
+    }
+    debug!(target: TRAFOCUS, "Focus {:?} -> {:?}", self.focused, id);
+    if let Some(old) = self.focused.filter(|old| scene_stack.contains(*old)) {
+      scene_stack.send_to(old, &Action::FocusOut, None, action_bus);
+    }
+    self.focused = id;
+    if let Some(new) = id {
+      scene_stack.send_to(new, &Action::FocusIn, None, action_bus);
+    }
+  }
+
+  // Back to a scene which had it (navigation pop), the indicator as it is
+  pub(crate) fn restore(&mut self, id: SceneID, scene_stack: &mut SceneStack, action_bus: &mut ActionBus) {
+    self.set(Some(id), self.visible, scene_stack, action_bus);
+  }
+
+  // Returns false if there was nowhere to go : nothing focusable (Tab), nothing in that direction (D-pad).
+  // The focus is then left as it is (indicator included) and the event to the scenes.
+  pub(crate) fn move_focus(&mut self, mv: FocusMove, scene_stack: &mut SceneStack, layout_manager: &LayoutManager, action_bus: &mut ActionBus) -> bool {
+    let ranks = layout_manager.dfs_ranks();
+    let order = scene_stack.focus_order(|node| ranks.get(&node).copied());
+    let current = self.focused.filter(|id| order.contains(id));
+
+    let target = match (mv, current.and_then(|id| scene_stack.zone(id))) {
+      (FocusMove::Next, _) => cycle(&order, current, false),
+      (FocusMove::Previous, _) => cycle(&order, current, true),
+      (_, Some(from)) => {
+        let candidates: Vec<(SceneID, Rect)> = order.iter()
+          .filter(|id| Some(**id) != current)
+          .filter_map(|id| scene_stack.zone(*id).map(|zone| (*id, zone)))
+          .collect();
+        nearest(from, &candidates, mv).or(current)
+      },
+      // Nothing focused (or no zone to start from) : start from the beginning
+      (_, None) => cycle(&order, current, false),
+    };
+    let moved = match mv {
+      FocusMove::Next | FocusMove::Previous => target.is_some(),
+      _ => target.is_some() && target != current
+    };
+    if moved {
+      self.set(target, true, scene_stack, action_bus);
+    }
+    moved
+  }
+
+  // A mouse press was handled by that scene (or by none) : it gets the focus if it can take it
+  pub(crate) fn clicked(&mut self, consumer: Option<SceneID>, scene_stack: &mut SceneStack, action_bus: &mut ActionBus) {
+    let target = consumer.filter(|id| scene_stack.can_focus(*id));
+    self.set(target, false, scene_stack, action_bus);
+  }
+
+  // Called once per frame : the focused scene may have been closed, hidden, disabled, covered by a modal...
+  pub(crate) fn validate(&mut self, scene_stack: &mut SceneStack, action_bus: &mut ActionBus) {
+    if self.focused.is_some_and(|id| !scene_stack.can_focus(id)) {
+      self.set(None, false, scene_stack, action_bus);
+    }
+  }
+
+  pub(crate) fn render_indicator(&self, scene_stack: &SceneStack, renderer: &mut Renderer) {
+    if !self.visible {
+      return;
+    }
+    let Some(zone) = self.focused.and_then(|id| scene_stack.zone(id)) else { return; };
+    renderer.canvas.set_draw_color(Color::RGB(255, 200, 0));
+    // 2 pixels wide, around the zone
+    for grow in 1..3 {
+      let rect = Rect::new(zone.x() - grow, zone.y() - grow, zone.width() + 2 * grow as u32, zone.height() + 2 * grow as u32);
+      let _ = renderer.canvas.draw_rect(rect);
+    }
+  }
+}
+
+// The next (or previous) scene in the Tab order, wrapping around
+fn cycle(order: &[SceneID], current: Option<SceneID>, backwards: bool) -> Option<SceneID> {
+  if order.is_empty() {
+    return None;
+  }
+  let len = order.len();
+  let idx = match (current.and_then(|id| order.iter().position(|o| *o == id)), backwards) {
+    (Some(pos), false) => (pos + 1) % len,
+    (Some(pos), true) => (pos + len - 1) % len,
+    (None, false) => 0,
+    (None, true) => len - 1,
+  };
+  Some(order[idx])
+}
+
+// The nearest zone in a direction. The distance across the direction counts double, so that
+// going right picks the zone on the same row rather than a closer one below.
+fn nearest(from: Rect, candidates: &[(SceneID, Rect)], mv: FocusMove) -> Option<SceneID> {
+  let (fx, fy) = (from.center().x(), from.center().y());
+  candidates.iter()
+    .filter_map(|(id, zone)| {
+      let (dx, dy) = (zone.center().x() - fx, zone.center().y() - fy);
+      let (along, across) = match mv {
+        FocusMove::Right => (dx, dy),
+        FocusMove::Left => (-dx, dy),
+        FocusMove::Down => (dy, dx),
+        FocusMove::Up => (-dy, dx),
+        FocusMove::Next | FocusMove::Previous => return None,
+      };
+      (along > 0).then_some((along + 2 * across.abs(), *id))
+    })
+    .min()
+    .map(|(_, id)| id)
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_cycle() {
+    let order = [4, 2, 7];
+    assert_eq!(cycle(&order, None, false), Some(4));
+    assert_eq!(cycle(&order, None, true), Some(7));
+    assert_eq!(cycle(&order, Some(4), false), Some(2));
+    assert_eq!(cycle(&order, Some(7), false), Some(4)); // Wraps around
+    assert_eq!(cycle(&order, Some(4), true), Some(7));
+    assert_eq!(cycle(&order, Some(99), false), Some(4)); // Not in the order anymore
+    assert_eq!(cycle(&[], Some(4), false), None);
+  }
+
+  #[test]
+  fn test_nearest() {
+    // A 2x2 grid of buttons, plus one far on the right but slightly lower
+    let candidates = [
+      (2, Rect::new(100, 0, 50, 20)),
+      (3, Rect::new(0, 40, 50, 20)),
+      (4, Rect::new(100, 40, 50, 20)),
+      (5, Rect::new(300, 10, 50, 20)),
+    ];
+    let from = Rect::new(0, 0, 50, 20);
+    assert_eq!(nearest(from, &candidates, FocusMove::Right), Some(2));
+    assert_eq!(nearest(from, &candidates, FocusMove::Down), Some(3));
+    assert_eq!(nearest(from, &candidates, FocusMove::Left), None);
+    assert_eq!(nearest(from, &candidates, FocusMove::Up), None);
+
+    let from = Rect::new(100, 0, 50, 20);
+    assert_eq!(nearest(from, &candidates[2..], FocusMove::Right), Some(5));
+  }
+}
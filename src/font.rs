@@ -1,14 +1,36 @@
-use std::{collections::HashMap, ffi::OsStr, fs, path::Path};
-use sdl2::ttf::{Font, Sdl2TtfContext};
+use std::{collections::HashMap, ffi::OsStr, path::{Path, PathBuf}};
+use sdl2::{rwops::RWops, ttf::{Font, Sdl2TtfContext}};
+
+use tracing::warn;
+
+use crate::vfs;
 
 pub struct FontStore<'a> {
   fonts: HashMap<String, Font<'a, 'static>>,
+  // What was loaded, to do it again (hot reloading)
+  ttf_context: Option<&'a Sdl2TtfContext>,
+  loaded: Vec<(PathBuf, u16, String)>, // Folder, size, category
 }
 
 impl<'a> FontStore<'a> {
   // (TODO?) clippy "you should consider adding a `Default` implementation for `FontStore<'a>`"
   pub fn new() -> Self {
-    Self { fonts: HashMap::new() }
+    Self { fonts: HashMap::new(), ttf_context: None, loaded: Vec::new() }
+  }
+
+  // Loads again the folders (the fonts changed on disk). Returns false if nothing was loaded yet.
+  // A font which cannot be loaded (e.g. a file being written) keeps its previous version.
+  pub fn reload(&mut self) -> bool {
+    let Some(ttf_context) = self.ttf_context else { return false; };
+    for (folder, size, category) in &self.loaded {
+      for (key, path) in fonts_in(folder, category) {
+        match load_font(ttf_context, &path, *size) {
+          Ok(font) => { self.fonts.insert(key, font); },
+          Err(e) => warn!(target: "hg::ttf", "Font {} not reloaded: {}", key, e)
+        }
+      }
+    }
+    true
   }
 
   pub fn get(&'a self, name: &str) -> &'a Font<'a, 'static> {
@@ -33,30 +55,34 @@ impl<'a> FontStore<'a> {
     // (TODO: I'll just have to decide what "small", "medium" or "big" means depending on the DPI & screen size)
   )
   {
-    // Read the directory entries
-    let entries = fs::read_dir(fonts_folder)
-      .unwrap_or_else(|e| panic!("{}", format!("Failed to read directory: {}", e)));
-
-    for entry in entries {
-      let entry = entry.unwrap_or_else(|e| panic!("{}", format!("Failed to read entry: {}", e)));
-      let path = entry.path();
-
-      // Check if it's a file ending in .ttf
-      if path.is_file() {
-        if let Some(ext) = path.extension() {
-          if ext == OsStr::new("ttf") {
-            if let Some(stem) = path.file_stem() { // filename without .ttf
-              let key = stem.to_string_lossy().to_string() + "_" + category;
-
-              // Load the font and put it in the hashmap
-              let font = ttf_context
-                .load_font(&path, font_size)
-                .unwrap_or_else(|e| panic!("{}", format!("Failed to load font: {}", e)));
-              self.fonts.insert(key, font);
-            }
-          }
-        }
-      }
+    self.ttf_context = Some(ttf_context);
+    self.loaded.push((fonts_folder.to_path_buf(), font_size, category.to_owned()));
+
+    for (key, path) in fonts_in(fonts_folder, category) {
+      // Load the font and put it in the hashmap
+      let font = load_font(ttf_context, &path, font_size)
+        .unwrap_or_else(|e| panic!("{}", format!("Failed to load font: {}", e)));
+      self.fonts.insert(key, font);
     }
   }
 }
+
+// The .ttf files of the folder, loose or archived, with their key in the store ("VcrOsdMono_small")
+fn fonts_in(fonts_folder: &Path, category: &str) -> Vec<(String, PathBuf)> {
+  vfs::list_dir(fonts_folder).into_iter()
+    .filter(|path| path.extension() == Some(OsStr::new("ttf")))
+    .filter_map(|path| {
+      let stem = path.file_stem()?.to_string_lossy().to_string(); // filename without .ttf
+      Some((stem + "_" + category, path))
+    })
+    .collect()
+}
+
+fn load_font<'a>(ttf_context: &'a Sdl2TtfContext, path: &Path, font_size: u16) -> Result<Font<'a, 'static>, String> {
+  match vfs::read_static(path) {
+    Some(bytes) => bytes.map_err(|e| e.to_string())
+      .and_then(RWops::from_bytes)
+      .and_then(|rw| ttf_context.load_font_from_rwops(rw, font_size)),
+    None => ttf_context.load_font(path, font_size)
+  }
+}
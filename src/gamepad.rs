@@ -0,0 +1,73 @@
+use sdl2::{controller::GameController, GameControllerSubsystem};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+const TRAPAD: &str = "hg::gamepad";
+
+// A connected gamepad, as seen by the user (and as recorded, cf. replay.rs)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GamepadInfo {
+  pub id: u32, // SDL instance id, the "which" of the controller events
+  pub name: String,
+  pub has_rumble: bool,
+}
+
+// Opens the game controllers as they are plugged (SDL also sends ControllerDeviceAdded for the
+// ones already connected at startup) and closes them when they are unplugged.
+pub(crate) struct GamepadManager {
+  subsystem: GameControllerSubsystem,
+  pads: Vec<GameController>,
+}
+
+impl GamepadManager {
+  pub(crate) fn new(subsystem: GameControllerSubsystem) -> Self {
+    Self { subsystem, pads: Vec::new() }
+  }
+
+  // ControllerDeviceAdded : which is the joystick index. Returns the new pad.
+  pub(crate) fn add(&mut self, joystick_index: u32) -> Option<GamepadInfo> {
+    match self.subsystem.open(joystick_index) {
+      Ok(pad) => {
+        if self.pads.iter().any(|p| p.instance_id() == pad.instance_id()) {
+          return None; // Already opened
+        }
+        let info = Self::info(&pad);
+        info!(target: TRAPAD, "Gamepad connected: {:?}", info);
+        self.pads.push(pad);
+        Some(info)
+      },
+      Err(e) => {
+        warn!(target: TRAPAD, "Cannot open the game controller {}: {}", joystick_index, e);
+        None
+      }
+    }
+  }
+
+  // ControllerDeviceRemoved : which is the instance id. Returns false if that pad was unknown.
+  pub(crate) fn remove(&mut self, id: u32) -> bool {
+    let before = self.pads.len();
+    self.pads.retain(|p| p.instance_id() != id);
+    if self.pads.len() == before {
+      return false;
+    }
+    info!(target: TRAPAD, "Gamepad {} disconnected", id);
+    true
+  }
+
+  pub(crate) fn list(&self) -> Vec<GamepadInfo> {
+    self.pads.iter().map(Self::info).collect()
+  }
+
+  // None : every pad
+  pub(crate) fn rumble(&mut self, pad: Option<u32>, low: u16, high: u16, duration_ms: u32) {
+    for p in self.pads.iter_mut().filter(|p| pad.is_none_or(|id| p.instance_id() == id)) {
+      if let Err(e) = p.set_rumble(low, high, duration_ms) {
+        warn!(target: TRAPAD, "Rumble failed on gamepad {}: {}", p.instance_id(), e);
+      }
+    }
+  }
+
+  fn info(pad: &GameController) -> GamepadInfo {
+    GamepadInfo { id: pad.instance_id(), name: pad.name(), has_rumble: pad.has_rumble() }
+  }
+}
@@ -0,0 +1,200 @@
+use tracing::debug;
+
+const TRAGEST: &str = "hg::gesture";
+
+// Tuning. Times in seconds, distances in window pixels.
+const TAP_MAX_TIME: f32 = 0.3;
+const LONG_PRESS_TIME: f32 = 0.5;
+const SLOP: f32 = 10.0; // A finger moving less than that is still
+const SWIPE_MIN_DIST: f32 = 50.0;
+const SWIPE_MAX_TIME: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SwipeDirection {
+  Left,
+  Right,
+  Up,
+  Down
+}
+
+// Recognized touch gestures, in window pixels. Given to the scene under the gesture (EventKind::Gesture).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+  Tap { x: i32, y: i32 },
+  LongPress { x: i32, y: i32 },
+  Swipe { direction: SwipeDirection, x: i32, y: i32, speed: f32 }, // x, y : where it started. Speed in px/s
+  Pinch { scale: f32, x: i32, y: i32 }, // Relative to the previous Pinch (> 1 : fingers moving apart). x, y : center
+}
+
+impl Gesture {
+  pub fn point(&self) -> (i32, i32) {
+    match *self {
+      Gesture::Tap { x, y } | Gesture::LongPress { x, y }
+      | Gesture::Swipe { x, y, .. } | Gesture::Pinch { x, y, .. } => (x, y)
+    }
+  }
+}
+
+struct Finger {
+  id: i64,
+  start: (f32, f32),
+  pos: (f32, f32),
+  start_time: f32,
+}
+
+// Turns the finger events into gestures. One finger : tap, long press or swipe.
+// Two fingers : pinch (the single finger gestures are cancelled).
+pub(crate) struct GestureRecognizer {
+  now: f32,
+  fingers: Vec<Finger>,
+  long_pressed: bool, // Already reported for the current touch
+  multi: bool, // Two fingers were down during the current touch
+  pinch_dist: Option<f32>,
+}
+
+impl GestureRecognizer {
+  pub(crate) fn new() -> Self {
+    Self { now: 0.0, fingers: Vec::new(), long_pressed: false, multi: false, pinch_dist: None }
+  }
+
+  pub(crate) fn finger_down(&mut self, id: i64, x: i32, y: i32) {
+    let pos = (x as f32, y as f32);
+    if self.fingers.is_empty() {
+      self.long_pressed = false;
+      self.multi = false;
+    }
+    self.fingers.push(Finger { id, start: pos, pos, start_time: self.now });
+    if self.fingers.len() >= 2 {
+      self.multi = true;
+      self.pinch_dist = self.two_fingers_dist();
+    }
+  }
+
+  pub(crate) fn finger_motion(&mut self, id: i64, x: i32, y: i32) -> Option<Gesture> {
+    let finger = self.fingers.iter_mut().find(|f| f.id == id)?;
+    finger.pos = (x as f32, y as f32);
+
+    let (Some(prev), Some(dist)) = (self.pinch_dist, self.two_fingers_dist()) else { return None; };
+    if prev <= 0.0 || (dist - prev).abs() < 1.0 {
+      return None;
+    }
+    self.pinch_dist = Some(dist);
+    let (a, b) = (self.fingers[0].pos, self.fingers[1].pos);
+    Some(Gesture::Pinch { scale: dist / prev, x: ((a.0 + b.0) / 2.0) as i32, y: ((a.1 + b.1) / 2.0) as i32 })
+  }
+
+  pub(crate) fn finger_up(&mut self, id: i64, x: i32, y: i32) -> Option<Gesture> {
+    let idx = self.fingers.iter().position(|f| f.id == id)?;
+    let finger = self.fingers.remove(idx);
+    if self.fingers.len() < 2 {
+      self.pinch_dist = None;
+    }
+    if self.multi || self.long_pressed {
+      return None;
+    }
+
+    let (dx, dy) = (x as f32 - finger.start.0, y as f32 - finger.start.1);
+    let dist = (dx * dx + dy * dy).sqrt();
+    let duration = self.now - finger.start_time;
+    let (sx, sy) = (finger.start.0 as i32, finger.start.1 as i32);
+
+    let gesture = if dist < SLOP && duration <= TAP_MAX_TIME {
+      Some(Gesture::Tap { x: sx, y: sy })
+    }
+    else if dist >= SWIPE_MIN_DIST && duration <= SWIPE_MAX_TIME {
+      let direction = if dx.abs() > dy.abs() {
+        if dx > 0.0 { SwipeDirection::Right } else { SwipeDirection::Left }
+      } else if dy > 0.0 { SwipeDirection::Down } else { SwipeDirection::Up };
+      Some(Gesture::Swipe { direction, x: sx, y: sy, speed: dist / duration.max(0.001) })
+    }
+    else { None };
+    debug!(target: TRAGEST, "Finger {} up: {:?}", id, gesture);
+    gesture
+  }
+
+  // Called every frame : the time goes on for the long press
+  pub(crate) fn update(&mut self, delta_time: f32) -> Option<Gesture> {
+    self.now += delta_time;
+    if self.multi || self.long_pressed || self.fingers.len() != 1 {
+      return None;
+    }
+    let f = &self.fingers[0];
+    let moved = ((f.pos.0 - f.start.0).powi(2) + (f.pos.1 - f.start.1).powi(2)).sqrt();
+    if moved < SLOP && self.now - f.start_time >= LONG_PRESS_TIME {
+      self.long_pressed = true;
+      debug!(target: TRAGEST, "Long press of finger {}", f.id);
+      return Some(Gesture::LongPress { x: f.start.0 as i32, y: f.start.1 as i32 });
+    }
+    None
+  }
+
+  fn two_fingers_dist(&self) -> Option<f32> {
+    if self.fingers.len() < 2 {
+      return None;
+    }
+    let (a, b) = (self.fingers[0].pos, self.fingers[1].pos);
+    Some(((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt())
+  }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_tap_and_long_press() {
+    let mut rec = GestureRecognizer::new();
+    rec.finger_down(1, 100, 100);
+    assert_eq!(rec.update(0.1), None);
+    rec.finger_motion(1, 103, 101); // Within the slop
+    assert_eq!(rec.finger_up(1, 103, 101), Some(Gesture::Tap { x: 100, y: 100 }));
+
+    rec.finger_down(1, 50, 50);
+    assert_eq!(rec.update(0.3), None);
+    assert_eq!(rec.update(0.3), Some(Gesture::LongPress { x: 50, y: 50 }));
+    assert_eq!(rec.update(0.3), None); // Only once
+    assert_eq!(rec.finger_up(1, 50, 50), None); // No tap after a long press
+
+    // Too slow for a tap, but not a long press either (it moved)
+    rec.finger_down(1, 50, 50);
+    rec.finger_motion(1, 70, 50);
+    rec.update(0.6);
+    assert_eq!(rec.finger_up(1, 52, 50), None);
+  }
+
+  #[test]
+  fn test_swipe() {
+    let mut rec = GestureRecognizer::new();
+    rec.finger_down(1, 100, 100);
+    rec.update(0.2);
+    rec.finger_motion(1, 160, 110);
+    let Some(Gesture::Swipe { direction, x, y, speed }) = rec.finger_up(1, 200, 110) else { panic!("no swipe") };
+    assert_eq!((direction, x, y), (SwipeDirection::Right, 100, 100));
+    assert!(speed > 400.0);
+
+    rec.finger_down(1, 100, 100);
+    rec.update(0.1);
+    assert!(matches!(rec.finger_up(1, 90, 20), Some(Gesture::Swipe { direction: SwipeDirection::Up, .. })));
+
+    // Too slow
+    rec.finger_down(1, 100, 100);
+    rec.update(1.0);
+    assert_eq!(rec.finger_up(1, 200, 100), None);
+  }
+
+  #[test]
+  fn test_pinch() {
+    let mut rec = GestureRecognizer::new();
+    rec.finger_down(1, 100, 100);
+    rec.finger_down(2, 200, 100);
+    assert_eq!(rec.finger_motion(2, 300, 100), Some(Gesture::Pinch { scale: 2.0, x: 200, y: 100 }));
+    assert_eq!(rec.finger_motion(2, 300, 100), None); // Didn't move
+    assert_eq!(rec.update(1.0), None); // No long press with two fingers
+    assert_eq!(rec.finger_up(2, 300, 100), None);
+    assert_eq!(rec.finger_motion(1, 150, 100), None); // One finger left : no pinch
+    assert_eq!(rec.finger_up(1, 150, 100), None); // ... and no swipe
+  }
+}
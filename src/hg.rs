@@ -1,9 +1,9 @@
-use std::time::{Duration, Instant};
+use std::{collections::HashMap, env, time::{Duration, Instant}};
 
-use sdl2::{event::{Event, WindowEvent}, image::Sdl2ImageContext, keyboard::Keycode, mixer::Sdl2MixerContext, mouse::MouseButton, pixels::Color, render::{Canvas, TextureCreator}, ttf::Sdl2TtfContext, video::{Window, WindowContext}, Sdl, VideoSubsystem};
+use sdl2::{controller::Button, event::{Event, WindowEvent}, image::Sdl2ImageContext, keyboard::{Keycode, Mod}, mixer::Sdl2MixerContext, pixels::Color, render::{Canvas, TextureCreator}, ttf::Sdl2TtfContext, video::{Window, WindowContext}, GameControllerSubsystem, Sdl, VideoSubsystem};
 use tracing::{debug, info, warn};
 //use taffy::print_tree;
-use crate::{action::{Action, EventKind}, action_bus::{ActionBus, ActionPriv}, egui_scene::EguiScene, font::FontStore, infraglobals, init, layout_manager::LayoutManager, mixer_manager::MixerManager, scene::{Scene, SceneID, SceneStack}, sprite::SpriteStore, Renderer};
+use crate::{action::{Action, EventKind}, action_bus::{ActionBus, ActionPriv}, egui_scene::EguiScene, focus::{FocusManager, FocusMove}, font::FontStore, gamepad::{GamepadInfo, GamepadManager}, gesture::{Gesture, GestureRecognizer}, hot_reload::{AssetChange, HotReloader}, infraglobals, init, input_map::InputMap, layout_manager::LayoutManager, mixer_manager::MixerManager, navigation::Navigator, pointer::{self, PointerTracker}, preload::{self, PreloadJob}, replay::{self, Player, Recorder, Replayed}, scene::{Scene, SceneFlags, SceneID, SceneStack}, sprite::SpriteStore, transition::{ActiveTransition, Transition}, vfs, Renderer};
 
 pub use crate::infraglobals::set_install_path;
 pub use crate::infraglobals::set_userdata_path;
@@ -14,8 +14,20 @@ pub enum HamID {
   SpriteID(usize)
 }
 
+// Why run_main_loop returned
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExitReason {
+  QuitRequested, // The window was closed (or the OS asked to quit) and no scene vetoed it
+  Quit(SceneID), // Action::Quit, pushed by that scene
+  ReplayFinished, // The whole recording was replayed
+}
+
 const TRAINIT: &str = "hg::init";
 const TRASCENE: &str = "hg::scene";
+const TRANAV: &str = "hg::nav";
+
+// Flags of the navigation entries which are not on top of their stack
+const NAV_BURIED: SceneFlags = SceneFlags::Hidden.union(SceneFlags::Paused).union(SceneFlags::InputDisabled);
 
 
 /** "Abstraction" of SDL2 for the user. */
@@ -24,6 +36,7 @@ pub struct HamSdl2 {
   _image_context: Sdl2ImageContext,
   ttf_context: Sdl2TtfContext,
   _video_subsystem: VideoSubsystem,
+  controller_subsystem: GameControllerSubsystem,
   _mixer_context: Sdl2MixerContext,
   canvas: Canvas<Window>,
   texture_creator: TextureCreator<WindowContext>,
@@ -32,12 +45,12 @@ pub struct HamSdl2 {
 
 impl HamSdl2 {
   pub fn new(title: &str, win_width: u32, win_heigt: u32) -> Self {
-    let (sdl_context, _image_context, ttf_context, _video_subsystem, _mixer_context, canvas) 
+    let (sdl_context, _image_context, ttf_context, _video_subsystem, controller_subsystem, _mixer_context, canvas) 
     = init::init_sdl2(title, win_width, win_heigt);
 
     let texture_creator = canvas.texture_creator();
     Self {
-      sdl_context, _image_context, ttf_context, _video_subsystem, _mixer_context, canvas, 
+      sdl_context, _image_context, ttf_context, _video_subsystem, controller_subsystem, _mixer_context, canvas, 
       window_dim: (win_width, win_heigt), texture_creator
     }
   }
@@ -52,15 +65,29 @@ pub struct HamGraph<'a> {
   pub(crate) layout_manager: LayoutManager,
   pub window_dim: (u32, u32),
   pub mixer_manager: MixerManager<'a>,
+  transition: Option<ActiveTransition<'a>>,
+  navigator: Navigator,
+  exit_reason: Option<ExitReason>,
+  pointer: PointerTracker,
+  focus: FocusManager,
+  input_map: InputMap,
+  gamepads: GamepadManager,
+  gestures: GestureRecognizer,
+  touch_owners: HashMap<i64, SceneID>, // The scene which handled the FingerDown gets that finger
+  recorder: Option<Recorder>,
+  player: Option<Player>,
+  preloads: Vec<PreloadJob>,
+  hot_reloader: Option<HotReloader>,
 }
   
 impl<'a> HamGraph<'a> {
   pub fn new(hamsdl2: &'a mut HamSdl2, mut root_scene: Box<dyn Scene>) -> Self {
+    vfs::mount_default(); // <install path>/assets.hgpack, made by hgpack
     let sprite_store = SpriteStore::new(&hamsdl2.texture_creator);
 
     info!(target: TRAINIT, "Initializing HAMGRAPH...");
     
-    let mut action_bus = ActionBus::new(sprite_store.shared_len());
+    let mut action_bus = ActionBus::new(sprite_store.shared_len(), sprite_store.shared_catalog());
     action_bus.prepare(1, 2); // Consider scene 1 (user root) with next scene ID 2
     root_scene.init(&mut action_bus);
 
@@ -70,19 +97,119 @@ impl<'a> HamGraph<'a> {
     let layout_manager = LayoutManager::new(wdim);
 
     let mut font_store = FontStore::new();
-    let scene_stack = SceneStack::new(root_scene, layout_manager.root_node_id);
+    let mut scene_stack = SceneStack::new(root_scene, layout_manager.root_node_id);
+    scene_stack.enter(1, &mut action_bus);
+    scene_stack.refresh_focus(&mut action_bus);
     
     // Todo : this should of course be multiplied by the UI scale. 
     font_store.load_default_sized_fonts(&hamsdl2.ttf_context, &infraglobals::get_ttf_path());
     
     let renderer = Renderer::new(&hamsdl2.sdl_context, &mut hamsdl2._video_subsystem, &mut hamsdl2.canvas, sprite_store, font_store, &hamsdl2.texture_creator);
-    Self {
+    let mut hg = Self {
       renderer,
       scene_stack, 
       action_bus, 
       layout_manager, 
       window_dim: hamsdl2.window_dim, 
-      mixer_manager: MixerManager::new()
+      mixer_manager: MixerManager::new(),
+      transition: None,
+      navigator: Navigator::new(),
+      exit_reason: None,
+      pointer: PointerTracker::new(),
+      focus: FocusManager::new(),
+      input_map: InputMap::load_user(InputMap::new()),
+      gamepads: GamepadManager::new(hamsdl2.controller_subsystem.clone()),
+      gestures: GestureRecognizer::new(),
+      touch_owners: HashMap::new(),
+      recorder: None,
+      player: None,
+      preloads: Vec::new(),
+      hot_reloader: None,
+    };
+
+    // HAMGRAPH_REPLAY=name replays userdata/replays/name.jsonl, HAMGRAPH_RECORD=name records it
+    if let Ok(name) = env::var("HAMGRAPH_REPLAY") {
+      hg.start_replay(&name).unwrap_or_else(|e| warn!(target: TRAINIT, "Cannot replay {}: {}", name, e));
+    }
+    else if let Ok(name) = env::var("HAMGRAPH_RECORD") {
+      hg.start_recording(&name).unwrap_or_else(|e| warn!(target: TRAINIT, "Cannot record {}: {}", name, e));
+    }
+    if env::var("HAMGRAPH_HOTRELOAD").is_ok_and(|v| !v.is_empty() && v != "0") {
+      hg.enable_hot_reload();
+    }
+    hg
+  }
+
+  // Development : the changed images, spritedesc.json, fonts and sounds are loaded again while the game runs
+  pub fn enable_hot_reload(&mut self) {
+    self.hot_reloader = Some(HotReloader::new(infraglobals::get_install_path()));
+  }
+
+  // Record the inputs to userdata/replays/<name>.jsonl (overwritten)
+  pub fn start_recording(&mut self, name: &str) -> Result<(), String> {
+    self.recorder = Some(Recorder::create(&replay::replay_path(name))?);
+    Ok(())
+  }
+
+  // Replay a recording instead of the real inputs. run_main_loop returns ExitReason::ReplayFinished at its end.
+  pub fn start_replay(&mut self, name: &str) -> Result<(), String> {
+    self.player = Some(Player::open(&replay::replay_path(name))?);
+    Ok(())
+  }
+
+  // The game's default input bindings. The ones saved by the player (Action::SaveInputMap) replace them.
+  pub fn set_input_defaults(&mut self, defaults: InputMap) {
+    self.input_map = InputMap::load_user(defaults);
+  }
+
+  // The connected gamepads
+  pub fn gamepads(&self) -> Vec<GamepadInfo> {
+    self.gamepads.list()
+  }
+
+  // Bytes of textures kept in memory : over it, the least recently used sheets are unloaded
+  // (and reloaded when needed). None (the default) : no limit
+  pub fn set_texture_budget(&mut self, budget: Option<usize>) {
+    self.renderer.sprite_store.set_texture_budget(budget);
+  }
+
+  // A few decoded textures per frame, and the progress to the scenes waiting for them
+  fn upload_preloads(&mut self) {
+    if self.preloads.is_empty() {
+      return;
+    }
+    let deadline = Instant::now() + preload::UPLOAD_BUDGET;
+    let mut jobs = std::mem::take(&mut self.preloads);
+    for job in &mut jobs {
+      let before = job.loaded;
+      self.renderer.sprite_store.upload_preloaded(job, deadline);
+      if job.loaded != before {
+        let progress = Action::PreloadProgress { loaded: job.loaded, total: job.total };
+        self.scene_stack.send_to(job.owner, &progress, None, &mut self.action_bus);
+      }
+      if job.is_done() {
+        debug!(target: TRASCENE, "Preload of scene {} done", job.owner);
+        self.scene_stack.send_to(job.owner, &Action::PreloadDone, None, &mut self.action_bus);
+      }
+    }
+    jobs.retain(|job| !job.is_done());
+    self.preloads = jobs;
+  }
+
+  fn hot_reload(&mut self, delta_time: f32) {
+    let Some(hr) = self.hot_reloader.as_mut() else { return; };
+    for change in hr.poll(delta_time) {
+      info!(target: TRAINIT, "Hot reloading: {:?}", change);
+      match change {
+        AssetChange::Image(file) => self.renderer.sprite_store.reload_image(&file),
+        AssetChange::SpriteDesc => self.renderer.sprite_store.reload_desc(),
+        AssetChange::Font => {
+          if self.renderer.font_store.reload() {
+            self.renderer.sprite_store.rerender_texts(&self.renderer.font_store);
+          }
+        },
+        AssetChange::Sound(file) => self.mixer_manager.forget(&file)
+      }
     }
   }
 
@@ -98,6 +225,123 @@ impl<'a> HamGraph<'a> {
     self.action_bus.prepare(id, self.scene_stack.next_scene_id());
    // self.action_bus.next_sprite_id = self.sprite_store.size();
     self.scene_stack.init(id, &mut self.action_bus);
+    self.scene_stack.enter(id, &mut self.action_bus);
+    self.scene_stack.refresh_focus(&mut self.action_bus);
+  }
+
+  // Remove a scene and its subtree (on_exit hooks are called)
+  fn close_scene(&mut self, id: SceneID) {
+    // Remove from layout if applicable 
+    if let Some(nodeid) = self.scene_stack.nodeid(id) {
+      self.layout_manager.remove_layout(nodeid);
+    }
+    // Remove scene from scene stack
+    let removed = self.scene_stack.close_scene(id, &mut self.action_bus);
+    self.scene_stack.refresh_focus(&mut self.action_bus);
+    self.renderer.sprite_store.free_texts_of(&removed);
+
+    // A transition whose screens disappear is just dropped
+    if self.transition.as_ref().is_some_and(|tr| removed.contains(&tr.outgoing) || removed.contains(&tr.incoming)) {
+      self.transition = None;
+    }
+    // The top of a navigation stack may have been closed directly
+    for revealed in self.navigator.forget(&removed) {
+      self.reveal_nav_entry(revealed);
+    }
+  }
+
+  // Hide (bury = true) or show again a navigation entry. 
+  // A hidden entry doesn't take space in the layout either. The flags the game set itself are kept.
+  fn bury_nav_entry(&mut self, id: SceneID, bury: bool) {
+    if bury {
+      let added = NAV_BURIED.difference(self.scene_stack.own_flags(id).unwrap_or(SceneFlags::empty()));
+      self.navigator.set_buried_flags(id, added);
+      self.scene_stack.set_flags(id, added, true);
+    }
+    else {
+      let added = self.navigator.take_buried_flags(id);
+      self.scene_stack.set_flags(id, added, false);
+    }
+    if let Some(nodeid) = self.scene_stack.nodeid(id) {
+      self.layout_manager.set_displayed(nodeid, !bury);
+    }
+    self.scene_stack.refresh_focus(&mut self.action_bus);
+  }
+
+  fn nav_push(&mut self, source: SceneID, scene: Box<dyn Scene>, id: SceneID, layer: usize, replace: bool) {
+    let host = self.navigator.host_of(source);
+    self.register_scene(layer, scene, id, host);
+    if replace {
+      if let Some(old) = self.navigator.replace(host, id) {
+        self.close_scene(old);
+      }
+    }
+    else if let Some(prev) = self.navigator.push(host, id, self.focus.focused()) {
+      self.bury_nav_entry(prev, true);
+    }
+  }
+
+  // Returns false if there was nothing to pop
+  fn nav_pop(&mut self, host: SceneID, to_root: bool) -> bool {
+    let popped = if to_root { 
+      self.navigator.pop_to_root(host) 
+    } else { 
+      self.navigator.pop(host).map(|(popped, revealed)| (vec![popped], revealed)) 
+    };
+    let Some((popped, revealed)) = popped else {
+      debug!(target: TRANAV, "Nothing to pop on host {}", host);
+      return false;
+    };
+    for id in popped {
+      self.close_scene(id);
+    }
+    self.reveal_nav_entry(revealed);
+    true
+  }
+
+  // Shown again, with the focus it had when it was buried
+  fn reveal_nav_entry(&mut self, id: SceneID) {
+    self.bury_nav_entry(id, false);
+    if let Some(focused) = self.navigator.take_focus(id).filter(|f| self.scene_stack.can_focus(*f)) {
+      self.focus.restore(focused, &mut self.scene_stack, &mut self.action_bus);
+    }
+  }
+
+  // Back button : pops the most recently used navigation stack
+  fn navigate_back(&mut self) -> bool {
+    match self.navigator.back_target() {
+      Some(host) => self.nav_pop(host, false),
+      None => false
+    }
+  }
+
+  // The new scene takes the place (layer, parent) of the old one, which is closed 
+  // at the end of the transition.
+  fn replace_scene(&mut self, old: SceneID, scene: Box<dyn Scene>, id: SceneID, transition: Transition) {
+    // Only one transition at a time : the running one ends now
+    if let Some(running) = self.transition.take() {
+      self.close_scene(running.outgoing);
+    }
+    let (layer, parent) = match self.scene_stack.layer_of(old) {
+      Some(layer) => (layer, self.scene_stack.parent(old)),
+      None => {
+        warn!(target: TRASCENE, "ReplaceScene: scene {} is already dead", old);
+        (0, 0)
+      }
+    };
+    self.register_scene(layer, scene, id, parent);
+
+    if let Transition::Cut = transition {
+      self.close_scene(old);
+      return;
+    }
+    // The old screen stays where it is and doesn't get input anymore
+    if let Some(nodeid) = self.scene_stack.nodeid(old) {
+      self.layout_manager.freeze_layout(nodeid);
+    }
+    self.scene_stack.set_flags(old, SceneFlags::InputDisabled, true);
+    self.scene_stack.refresh_focus(&mut self.action_bus);
+    self.transition = Some(ActiveTransition::new(transition, old, id));
   }
 
   fn handle_user_action(&mut self, action_p: ActionPriv) { // TODO err mgt 
@@ -118,6 +362,32 @@ impl<'a> HamGraph<'a> {
         }
         else { panic!("Contract error [83]"); } 
       }
+      Action::ReplaceScene { with, transition } => {
+        if let Some(HamID::SceneID(scid)) = action_p.back_id {
+          self.replace_scene(action_p.source_scene, with, scid, transition);
+        }
+        else { panic!("Contract error [84]"); } 
+      },
+      Action::NavPush { scene, layer } => {
+        if let Some(HamID::SceneID(scid)) = action_p.back_id {
+          self.nav_push(action_p.source_scene, scene, scid, layer, false);
+        }
+        else { panic!("Contract error [85]"); } 
+      },
+      Action::NavReplace { scene, layer } => {
+        if let Some(HamID::SceneID(scid)) = action_p.back_id {
+          self.nav_push(action_p.source_scene, scene, scid, layer, true);
+        }
+        else { panic!("Contract error [86]"); } 
+      },
+      Action::NavPop => {
+        let host = self.navigator.host_of(action_p.source_scene);
+        self.nav_pop(host, false);
+      },
+      Action::NavPopToRoot => {
+        let host = self.navigator.host_of(action_p.source_scene);
+        self.nav_pop(host, true);
+      },
       Action::CreateText { font, size, text } => {
         self.layout_manager.update_layout(); 
         let (_id_layout, nodeid_layout) = self.scene_stack.get_first_with_layout(action_p.source_scene);
@@ -141,15 +411,48 @@ impl<'a> HamGraph<'a> {
           max_width,
         );
 
-        self.renderer.sprite_store.commit_ttf_texture();
+        self.renderer.sprite_store.commit_ttf_texture(action_p.source_scene);
       },                          
       Action::CloseCurrentScene => {
-        // Remove from layout if applicable 
-        if let Some(nodeid) = self.scene_stack.nodeid(action_p.source_scene) {
-          self.layout_manager.remove_layout(nodeid);
+        self.close_scene(action_p.source_scene);
+      },
+      Action::SetSceneFlags { target_id, flags, enabled } => {
+        let target = target_id.unwrap_or(action_p.source_scene);
+        debug!(target: TRASCENE, "Set flags {:?}={} on scene {}", flags, enabled, target);
+        self.scene_stack.set_flags(target, flags, enabled);
+        self.scene_stack.refresh_focus(&mut self.action_bus);
+      },
+      Action::SetFocus { target_id } => {
+        let target = target_id.unwrap_or(action_p.source_scene);
+        if self.scene_stack.can_focus(target) {
+          self.focus.set(Some(target), true, &mut self.scene_stack, &mut self.action_bus);
+        } else {
+          warn!(target: TRASCENE, "Scene {} cannot get the focus (not focusable, hidden or disabled)", target);
+        }
+      },
+      Action::RebindInput { name, binding } => {
+        info!(target: TRASCENE, "Input '{}' rebound to {:?}", name, binding);
+        self.input_map.rebind(&name, binding);
+      },
+      Action::SaveInputMap => {
+        self.input_map.save_user();
+      },
+      Action::Preload { sheets } => {
+        let job = self.renderer.sprite_store.start_preload(action_p.source_scene, &sheets);
+        self.preloads.push(job);
+      },
+      Action::Rumble { pad, low, high, duration_ms } => {
+        self.gamepads.rumble(pad, low, high, duration_ms);
+      },
+      Action::StartDrag { payload } => {
+        let source = action_p.source_scene;
+        if !self.pointer.start_drag(source, payload, &mut self.scene_stack, &mut self.action_bus) {
+          warn!(target: TRASCENE, "Scene {} cannot start a drag: it does not hold the pointer", source);
         }
-        // Remove scene from scene stack
-        self.scene_stack.remove_scene(action_p.source_scene);
+      },
+      Action::Quit => {
+        info!(target: TRASCENE, "Quit requested by scene {}", action_p.source_scene);
+        self.exit_reason = Some(ExitReason::Quit(action_p.source_scene));
       },
       Action::StartMusic { track, loops } => {
         self.mixer_manager.play_music(&track, loops);
@@ -161,7 +464,7 @@ impl<'a> HamGraph<'a> {
         self.mixer_manager.stop_music();
       }
       // Meant to be sent back to another scene 
-      Action::ButtonPressed => {
+      Action::ButtonPressed | Action::AnimationFinished { .. } => {
         self.scene_stack.propagate_ham_to_subscribers(&mut self.action_bus, action_p);
       },
       Action::RequestLayout (lay) => {
@@ -173,7 +476,216 @@ impl<'a> HamGraph<'a> {
     }
   }
 
-  pub fn run_main_loop(&mut self)
+  // GamepadConnected / GamepadDisconnected to every scene. Recorded as such : a replay doesn't open the pads.
+  fn gamepad_hotplug(&mut self, action: Action) {
+    if let Some(rec) = self.recorder.as_mut() {
+      rec.record_hotplug(&action);
+    }
+    self.scene_stack.deliver_to_all(&mut self.action_bus, &action);
+  }
+
+  fn handle_sdl_event(&mut self, event: Event) {
+    if let Some(rec) = self.recorder.as_mut() {
+      rec.record(&event);
+    }
+    // Propagate to egui 
+    self.renderer.egui_platform.handle_event(&event, &self.renderer.sdl_context, &self.renderer.sdl_video);
+    if pointer::is_touch_mouse(&event) {
+      return;
+    }
+    self.pointer.handle_event(&event);
+    for (name, pressed) in self.input_map.translate(&event) {
+      self.dispatch_input(name, pressed);
+    }
+
+    let event_kind = match event {
+      Event::Quit {..} => {
+        self.request_quit();
+        return;
+      }, 
+
+      // Back button. If there is nothing to go back to, Escape is a key like the others
+      Event::KeyDown { keycode: Some(Keycode::Escape), repeat: false, .. } => {
+        if self.navigate_back() { return; }
+        EventKind::SdlKeyboard
+      },
+      Event::ControllerButtonDown { button: Button::B, .. } => {
+        if self.navigate_back() { return; }
+        EventKind::SdlController
+      },
+
+      // Focus navigation. With nothing focusable, Tab is a key like the others
+      Event::KeyDown { keycode: Some(Keycode::Tab), keymod, .. } => {
+        let backwards = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+        if self.move_focus(if backwards { FocusMove::Previous } else { FocusMove::Next }) { return; }
+        EventKind::SdlKeyboard
+      },
+      // The D-pad is for the game when the focus cannot move
+      Event::ControllerButtonDown { button: button @ (Button::DPadUp | Button::DPadDown | Button::DPadLeft | Button::DPadRight), .. } => {
+        let moved = self.move_focus(match button {
+          Button::DPadUp => FocusMove::Up,
+          Button::DPadDown => FocusMove::Down,
+          Button::DPadLeft => FocusMove::Left,
+          _ => FocusMove::Right,
+        });
+        if moved { return; }
+        EventKind::SdlController
+      },
+      Event::ControllerButtonDown {..} | Event::ControllerButtonUp {..} 
+      | Event::ControllerAxisMotion {..} => EventKind::SdlController,
+
+      Event::FingerDown {..} | Event::FingerMotion {..} | Event::FingerUp {..} | Event::MultiGesture {..} => {
+        self.dispatch_touch(event);
+        return;
+      },
+
+      // Gamepads hot-plug : every scene hears about it (modal or not)
+      Event::ControllerDeviceAdded { which, .. } => {
+        if let Some(pad) = self.gamepads.add(which) {
+          self.gamepad_hotplug(Action::GamepadConnected { pad });
+        }
+        return;
+      },
+      Event::ControllerDeviceRemoved { which, .. } => {
+        if self.gamepads.remove(which) {
+          self.gamepad_hotplug(Action::GamepadDisconnected { id: which });
+        }
+        return;
+      },
+
+      Event::MouseButtonDown {..} | Event::MouseButtonUp {..} => EventKind::SdlMouseClick, 
+      Event::MouseMotion {..} => EventKind::SdlMouseHover,
+      Event::MouseWheel {..} => EventKind::SdlMouseWheel,
+      Event::KeyDown {..} | Event::KeyUp {..} => EventKind::SdlKeyboard,
+      Event::TextInput {..} | Event::TextEditing {..} => EventKind::SdlTextInput,
+
+      Event::Window { win_event: WindowEvent::Resized(w, h), ..} => {
+        // Window has been resized : update the UI tree 
+        self.layout_manager.set_new_window_size((w as u32, h as u32)); // TODO important manage min 
+        return;
+      }
+      Event::Window { win_event: WindowEvent::FocusGained | WindowEvent::FocusLost 
+        | WindowEvent::Enter | WindowEvent::Leave, .. } => EventKind::SdlMisc,
+      _ => { return; /* Nothing for now */ }
+    };
+
+    // Here we really want to propagate the event e.g. MouseButtonDown
+    self.dispatch_sdl_event(event, event_kind);
+  }
+
+  fn dispatch_sdl_event(&mut self, event: Event, event_kind: EventKind) {
+    // While the pointer is captured, the captor gets the motion and the release wherever the pointer is
+    if let Some(captor) = self.pointer.captor() {
+      match event {
+        Event::MouseMotion { x, y, .. } => {
+          self.pointer.drag_over(x, y, &mut self.scene_stack, &mut self.action_bus);
+          self.scene_stack.send_to(captor, &Action::SdlEvent(event), None, &mut self.action_bus);
+          return;
+        },
+        Event::MouseButtonUp { mouse_btn, x, y, .. } if self.pointer.is_capture_button(mouse_btn) => {
+          self.scene_stack.send_to(captor, &Action::SdlEvent(event), None, &mut self.action_bus);
+          self.pointer.release(x, y, &mut self.scene_stack, &mut self.action_bus);
+          return;
+        },
+        _ => {}
+      }
+    }
+
+    // The keyboard, the text input and the gamepad go to the focused scene first (e.g. A activates it)
+    let focused = self.focus.focused()
+      .filter(|_| event_kind.intersects(EventKind::SdlKeyboard | EventKind::SdlTextInput | EventKind::SdlController));
+    if let Some(id) = focused {
+      let action = Action::SdlEvent(event.clone());
+      if self.scene_stack.route(&mut self.action_bus, &action, &event_kind, id, true).is_some() {
+        return;
+      }
+    }
+
+    let pressed = match event {
+      Event::MouseButtonDown { mouse_btn, .. } => Some(mouse_btn),
+      _ => None
+    };
+    let consumer = self.scene_stack.propagate_sdl2_except(&mut self.action_bus, Action::SdlEvent(event), event_kind, focused.as_slice());
+    if let Some(button) = pressed {
+      self.focus.clicked(consumer, &mut self.scene_stack, &mut self.action_bus);
+      if let Some(id) = consumer {
+        self.pointer.capture(id, button);
+      }
+    }
+  }
+
+  // The touch coordinates are normalized (0..1) : hit-test in window pixels.
+  // Like the pointer capture, a finger belongs to the scene which handled its FingerDown.
+  fn dispatch_touch(&mut self, event: Event) {
+    let (w, h) = self.renderer.canvas.window().size();
+    let to_px = |x: f32, y: f32| ((x * w as f32) as i32, (y * h as f32) as i32);
+
+    let (finger, (x, y)) = match event {
+      Event::FingerDown { finger_id, x, y, .. } | Event::FingerMotion { finger_id, x, y, .. } 
+      | Event::FingerUp { finger_id, x, y, .. } => (Some(finger_id), to_px(x, y)),
+      Event::MultiGesture { x, y, .. } => (None, to_px(x, y)),
+      _ => return
+    };
+    let gesture = match event {
+      Event::FingerDown { finger_id, .. } => { self.gestures.finger_down(finger_id, x, y); None },
+      Event::FingerMotion { finger_id, .. } => self.gestures.finger_motion(finger_id, x, y),
+      Event::FingerUp { finger_id, .. } => self.gestures.finger_up(finger_id, x, y),
+      _ => None
+    };
+    let is_down = matches!(event, Event::FingerDown {..});
+    let is_up = matches!(event, Event::FingerUp {..});
+
+    let owner = finger.and_then(|f| self.touch_owners.get(&f).copied());
+    let action = Action::SdlEvent(event);
+    match owner {
+      Some(id) => { self.scene_stack.send_to(id, &action, None, &mut self.action_bus); },
+      None => {
+        let consumer = self.scene_stack.propagate_at(&mut self.action_bus, action, EventKind::SdlTouch, Some((x, y)), &[]);
+        if let (true, Some(f), Some(id)) = (is_down, finger, consumer) {
+          self.touch_owners.insert(f, id);
+        }
+      }
+    }
+    if let (true, Some(f)) = (is_up, finger) {
+      self.touch_owners.remove(&f);
+    }
+    if let Some(g) = gesture {
+      self.dispatch_gesture(g);
+    }
+  }
+
+  fn dispatch_gesture(&mut self, gesture: Gesture) {
+    debug!(target: TRASCENE, "Gesture {:?}", gesture);
+    let point = gesture.point();
+    self.scene_stack.propagate_at(&mut self.action_bus, Action::Gesture(gesture), EventKind::Gesture, Some(point), &[]);
+  }
+
+  // Logical inputs go to the focused scene first, then to the subscribers
+  fn dispatch_input(&mut self, name: String, pressed: bool) {
+    let action = Action::Input { name, pressed };
+    let focused = self.focus.focused();
+    if let Some(id) = focused 
+      && self.scene_stack.route(&mut self.action_bus, &action, &EventKind::Input, id, true).is_some() {
+      return;
+    }
+    self.scene_stack.propagate_sdl2_except(&mut self.action_bus, action, EventKind::Input, focused.as_slice());
+  }
+
+  fn move_focus(&mut self, mv: FocusMove) -> bool {
+    self.focus.move_focus(mv, &mut self.scene_stack, &self.layout_manager, &mut self.action_bus)
+  }
+
+  // The scenes may veto the quit request
+  fn request_quit(&mut self) {
+    if self.scene_stack.broadcast(&mut self.action_bus, &Action::QuitRequested) {
+      info!(target: TRASCENE, "Quit request vetoed");
+    }
+    else {
+      self.exit_reason = Some(ExitReason::QuitRequested);
+    }
+  }
+
+  pub fn run_main_loop(&mut self) -> ExitReason
   {
     let mut event_pump = self.renderer.sdl_context.event_pump().unwrap_or_else(|e| {
       crate::errors::prompt_err_and_panic("SDL initialization error, no event pump", &e, None);
@@ -183,32 +695,31 @@ impl<'a> HamGraph<'a> {
     let mut last_update = Instant::now(); 
 
     'hamloop: loop {
-      // 1. HANDLE EVENTS
-      for event in event_pump.poll_iter() {
-        let event_kind;
-        // Propagate to egui 
-        self.renderer.egui_platform.handle_event(&event, &self.renderer.sdl_context, &self.renderer.sdl_video);
-
-        match event {
-          Event::Quit {..} |
-          Event::KeyDown { keycode: Some(Keycode::Escape), .. } => { break 'hamloop }, // LEGACY TODO 
-            
-            // this won't be needed once the weird stuff will have been filtered.  TODO 
-          Event::MouseButtonDown {mouse_btn: MouseButton::Left, ..} 
-          | Event::MouseButtonUp {mouse_btn: MouseButton::Left, ..} => {
-            event_kind = EventKind::SdlMouseClick;
-          }, 
-          Event::Window { win_event: WindowEvent::Resized(w, h), ..} => {
-            // Window has been resized : update the UI tree 
-            self.layout_manager.set_new_window_size((w as u32, h as u32)); // TODO important manage min 
-            continue;
+      // 1. HANDLE EVENTS (the recorded ones when replaying)
+      let mut replayed_dt = None;
+      match self.player.as_mut().map(|p| p.next_frame()) {
+        Some(Some((dt, events))) => {
+          replayed_dt = Some(dt);
+          for replayed in events {
+            match replayed {
+              Replayed::Event(event) => self.handle_sdl_event(event),
+              Replayed::GamepadConnected(pad) => self.gamepad_hotplug(Action::GamepadConnected { pad }),
+              Replayed::GamepadDisconnected(id) => self.gamepad_hotplug(Action::GamepadDisconnected { id }),
+            }
+          }
+          // The real inputs are ignored, but the window can still be closed
+          for event in event_pump.poll_iter() {
+            if let Event::Quit {..} = event { self.exit_reason = Some(ExitReason::QuitRequested); }
+          }
+        },
+        Some(None) => {
+          self.exit_reason = Some(ExitReason::ReplayFinished);
+        },
+        None => {
+          for event in event_pump.poll_iter() {
+            self.handle_sdl_event(event);
           }
-          _ => { continue; /* Nothing for now */ }
         }
-
-        // Here we really want to propagate the event e.g. MouseButtonDown
-        let action = Action::SdlEvent(event);
-        self.scene_stack.propagate_sdl2_to_subscribers(&mut self.action_bus, action, event_kind);
       }
 
       // 2. PROCESS ACTIONS that were ordered by the input handlers 
@@ -223,12 +734,29 @@ impl<'a> HamGraph<'a> {
           self.handle_user_action(a);
         }
       }
+      if let Some(reason) = self.exit_reason.take() {
+        info!(target: TRAINIT, "Leaving the main loop: {:?}", reason);
+        break 'hamloop reason;
+      }
 
       // 3. UPDATE GAME LOGIC
       let now = Instant::now(); // todo ... where should it be?
-      let delta_time = now.duration_since(last_update).as_secs_f32();
+      // When replaying, the recorded delta time, so that the scenes update exactly the same way
+      let delta_time = replayed_dt.unwrap_or(now.duration_since(last_update).as_secs_f32());
       last_update = now;
+      if let Some(rec) = self.recorder.as_mut() {
+        rec.end_frame(delta_time);
+      }
       self.scene_stack.update_all(delta_time, &mut self.action_bus);
+      if let Some(g) = self.gestures.update(delta_time) {
+        self.dispatch_gesture(g); // Long press
+      }
+      if self.transition.as_mut().is_some_and(|tr| tr.advance(delta_time)) {
+        let done = self.transition.take().unwrap();
+        self.close_scene(done.outgoing);
+      }
+      self.upload_preloads();
+      self.hot_reload(delta_time);
 
         // Handle only prioritary actions here 
       loop {
@@ -247,6 +775,9 @@ impl<'a> HamGraph<'a> {
       if layout_changed {
         self.scene_stack.update_layout(&self.layout_manager);
       }
+      // Hovering (after the layout : scenes may have moved under the pointer)
+      self.pointer.update(&mut self.scene_stack, &mut self.action_bus);
+      self.focus.validate(&mut self.scene_stack, &mut self.action_bus);
 
       // 4. DRAW
       self.renderer.canvas.set_draw_color(Color::RGB(0, 0, 0));
@@ -256,7 +787,12 @@ impl<'a> HamGraph<'a> {
       // .. only if there are actually immediate widgets pushed (TODO)
       
       self.renderer.begin_egui_pass();
-      self.scene_stack.render_all(&mut self.renderer, &mut self.action_bus);
+      if let Some(tr) = self.transition.as_mut() {
+        tr.render(&mut self.scene_stack, &mut self.renderer, &mut self.action_bus);
+      } else {
+        self.scene_stack.render_all(&mut self.renderer, &mut self.action_bus);
+      }
+      self.focus.render_indicator(&self.scene_stack, &mut self.renderer);
       self.renderer.end_egui_pass_and_paint();
       
 
@@ -265,7 +801,8 @@ impl<'a> HamGraph<'a> {
 
       // Maintain a consistent frame rate
       let frame_duration = now.elapsed();
-      if frame_duration < target_frame_duration { // TODO not needed with VSYNC ?
+      // A replay runs as fast as possible
+      if frame_duration < target_frame_duration && self.player.is_none() { // TODO not needed with VSYNC ?
         std::thread::sleep(target_frame_duration - frame_duration);
       } // else application is quite overwhelmed! ... 
     }
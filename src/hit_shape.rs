@@ -0,0 +1,159 @@
+use std::rc::Rc;
+
+use sdl2::{pixels::PixelFormatEnum, rect::Rect, render::BlendMode, surface::Surface};
+
+use crate::{infraglobals, utils::is_point_in_rect, vfs};
+
+// The clickable shape of a scene (cf. Scene::hit_shape), in window coordinates.
+// The engine uses it for the clicks, the hovering, the drop targets and the touch.
+#[derive(Clone, Debug)]
+pub enum HitShape {
+  Rect(Rect),
+  Circle { x: i32, y: i32, radius: u32 }, // Center
+  Polygon(Vec<(i32, i32)>), // Any simple polygon (not necessarily convex)
+  AlphaMask { rect: Rect, mask: Rc<AlphaMask> }, // The mask is stretched to the rect
+}
+
+impl HitShape {
+  pub fn contains(&self, x: i32, y: i32) -> bool {
+    match self {
+      HitShape::Rect(rect) => is_point_in_rect(rect, x, y),
+      HitShape::Circle { x: cx, y: cy, radius } => {
+        let (dx, dy) = ((x - cx) as i64, (y - cy) as i64);
+        dx * dx + dy * dy <= (*radius as i64) * (*radius as i64)
+      },
+      HitShape::Polygon(points) => is_point_in_polygon(points, x, y),
+      HitShape::AlphaMask { rect, mask } => {
+        if !is_point_in_rect(rect, x, y) {
+          return false;
+        }
+        // Back to the mask coordinates
+        let mx = (x - rect.x()) as u64 * mask.width as u64 / rect.width() as u64;
+        let my = (y - rect.y()) as u64 * mask.height as u64 / rect.height() as u64;
+        mask.is_opaque(mx as u32, my as u32)
+      }
+    }
+  }
+
+  // The bounding box
+  pub fn bounds(&self) -> Option<Rect> {
+    match self {
+      HitShape::Rect(rect) | HitShape::AlphaMask { rect, .. } => Some(*rect),
+      HitShape::Circle { x, y, radius } => Some(Rect::new(x - *radius as i32, y - *radius as i32, radius * 2, radius * 2)),
+      HitShape::Polygon(points) => Rect::from_enclose_points(
+        &points.iter().map(|(x, y)| sdl2::rect::Point::new(*x, *y)).collect::<Vec<_>>(), None),
+    }
+  }
+}
+
+// Even-odd rule
+fn is_point_in_polygon(points: &[(i32, i32)], x: i32, y: i32) -> bool {
+  let (px, py) = (x as f64 + 0.5, y as f64 + 0.5); // Center of the pixel
+  let mut inside = false;
+  let mut j = points.len().wrapping_sub(1);
+  for i in 0..points.len() {
+    let (xi, yi) = (points[i].0 as f64, points[i].1 as f64);
+    let (xj, yj) = (points[j].0 as f64, points[j].1 as f64);
+    if (yi > py) != (yj > py) && px < (xj - xi) * (py - yi) / (yj - yi) + xi {
+      inside = !inside;
+    }
+    j = i;
+  }
+  inside
+}
+
+// Which pixels of an image are opaque enough to be clicked
+#[derive(Debug)]
+pub struct AlphaMask {
+  width: u32,
+  height: u32,
+  opaque: Vec<bool>,
+}
+
+impl AlphaMask {
+  // From RGBA8888 pixels (4 bytes per pixel, rows of pitch bytes)
+  pub fn from_rgba(width: u32, height: u32, pitch: usize, pixels: &[u8], threshold: u8) -> Self {
+    let mut opaque = Vec::with_capacity((width * height) as usize);
+    for y in 0..height as usize {
+      for x in 0..width as usize {
+        opaque.push(pixels[y * pitch + x * 4 + 3] >= threshold);
+      }
+    }
+    Self { width, height, opaque }
+  }
+
+  pub fn from_surface(surface: &Surface, threshold: u8) -> Result<Self, String> {
+    // ABGR8888 is R, G, B, A in memory
+    let rgba = surface.convert_format(PixelFormatEnum::ABGR8888)?;
+    let (w, h, pitch) = (rgba.width(), rgba.height(), rgba.pitch() as usize);
+    let pixels = rgba.without_lock().ok_or("Surface must be locked")?;
+    Ok(Self::from_rgba(w, h, pitch, pixels, threshold))
+  }
+
+  // From an image of the img folder. src : the part of the image (e.g. a sprite of the sheet)
+  pub fn load(file: &str, src: Option<Rect>, threshold: u8) -> Result<Self, String> {
+    // RGBA first : an indexed (palette) image has no alpha to copy
+    let mut surface = vfs::load_surface(&infraglobals::get_img_path().join(file))?.convert_format(PixelFormatEnum::ABGR8888)?;
+    match src {
+      Some(src) => {
+        surface.set_blend_mode(BlendMode::None)?; // Copied as is, alpha included
+        let mut part = Surface::new(src.width(), src.height(), PixelFormatEnum::ABGR8888)?;
+        surface.blit(src, &mut part, None)?;
+        Self::from_surface(&part, threshold)
+      },
+      None => Self::from_surface(&surface, threshold)
+    }
+  }
+
+  pub fn is_opaque(&self, x: u32, y: u32) -> bool {
+    x < self.width && y < self.height && self.opaque[(y * self.width + x) as usize]
+  }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_shapes() {
+    let rect = HitShape::Rect(Rect::new(10, 10, 20, 20));
+    assert!(rect.contains(10, 10));
+    assert!(!rect.contains(30, 10));
+
+    let circle = HitShape::Circle { x: 50, y: 50, radius: 10 };
+    assert!(circle.contains(50, 50));
+    assert!(circle.contains(57, 57));
+    assert!(!circle.contains(58, 58)); // In the bounding box corner only
+    assert_eq!(circle.bounds(), Some(Rect::new(40, 40, 20, 20)));
+
+    // A "L" shape
+    let poly = HitShape::Polygon(vec![(0, 0), (10, 0), (10, 30), (30, 30), (30, 40), (0, 40)]);
+    assert!(poly.contains(5, 5));
+    assert!(poly.contains(25, 35));
+    assert!(!poly.contains(25, 5)); // Inside the bounding box, outside the L
+    assert!(!poly.contains(-1, 5));
+  }
+
+  #[test]
+  fn test_alpha_mask() {
+    // 2x2 image, pitch of 12 bytes : only the top left and bottom right pixels are opaque
+    let mut pixels = vec![0u8; 24];
+    pixels[3] = 255;
+    pixels[12 + 4 + 3] = 200;
+    let mask = Rc::new(AlphaMask::from_rgba(2, 2, 12, &pixels, 128));
+    assert!(mask.is_opaque(0, 0));
+    assert!(!mask.is_opaque(1, 0));
+    assert!(mask.is_opaque(1, 1));
+    assert!(!mask.is_opaque(2, 2));
+
+    // Stretched to 20x20 at (100, 100)
+    let shape = HitShape::AlphaMask { rect: Rect::new(100, 100, 20, 20), mask };
+    assert!(shape.contains(105, 105));
+    assert!(!shape.contains(115, 105));
+    assert!(shape.contains(119, 119));
+    assert!(!shape.contains(99, 99));
+  }
+}
@@ -0,0 +1,127 @@
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, time::SystemTime};
+
+use tracing::info;
+
+const TRAHOT: &str = "hg::hotreload";
+
+const POLL_INTERVAL: f32 = 0.5; // Seconds
+
+// What changed under the install path
+#[derive(Debug, PartialEq)]
+pub(crate) enum AssetChange {
+  Image(PathBuf), // Relative to the img folder
+  SpriteDesc, // spritedesc.json or an imported atlas
+  Font,
+  Sound(String), // Music or sfx, relative to its folder
+}
+
+// Development helper (HAMGRAPH_HOTRELOAD=1 or HamGraph::enable_hot_reload) : polls the modification
+// times of the img, conf, font, music and sfx folders
+pub(crate) struct HotReloader {
+  root: PathBuf,
+  mtimes: HashMap<PathBuf, SystemTime>,
+  since_poll: f32,
+}
+
+impl HotReloader {
+  pub(crate) fn new(root: &Path) -> Self {
+    info!(target: TRAHOT, "Watching the assets of {:?}", root);
+    let mut hr = Self { root: root.to_path_buf(), mtimes: HashMap::new(), since_poll: 0.0 };
+    hr.mtimes = hr.scan();
+    hr
+  }
+
+  // Called every frame, the disk is only scanned every POLL_INTERVAL
+  pub(crate) fn poll(&mut self, delta_time: f32) -> Vec<AssetChange> {
+    self.since_poll += delta_time;
+    if self.since_poll < POLL_INTERVAL {
+      return Vec::new();
+    }
+    self.since_poll = 0.0;
+
+    let mtimes = self.scan();
+    let mut changes = Vec::new();
+    for (path, mtime) in &mtimes {
+      if self.mtimes.get(path) != Some(mtime) {
+        info!(target: TRAHOT, "{:?} changed", path);
+        if let Some(change) = classify(&self.root, path) && !changes.contains(&change) {
+          changes.push(change);
+        }
+      }
+    }
+    self.mtimes = mtimes;
+    changes
+  }
+
+  fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+    let mut mtimes = HashMap::new();
+    for dir in ["img", "conf", "font", "music", "sfx"] {
+      scan_dir(&self.root.join(dir), &mut mtimes);
+    }
+    mtimes
+  }
+}
+
+fn scan_dir(dir: &Path, mtimes: &mut HashMap<PathBuf, SystemTime>) {
+  let Ok(entries) = fs::read_dir(dir) else { return; };
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if path.is_dir() {
+      scan_dir(&path, mtimes);
+    }
+    else if let Ok(mtime) = entry.metadata().and_then(|m| m.modified()) {
+      mtimes.insert(path, mtime);
+    }
+  }
+}
+
+fn classify(root: &Path, path: &Path) -> Option<AssetChange> {
+  let rel = path.strip_prefix(root).ok()?;
+  let mut components = rel.components();
+  let dir = components.next()?.as_os_str().to_str()?;
+  let file = components.as_path();
+  let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+  match dir {
+    "img" if ext == "json" => Some(AssetChange::SpriteDesc),
+    "img" => Some(AssetChange::Image(file.to_path_buf())),
+    "conf" if file == Path::new("spritedesc.json") => Some(AssetChange::SpriteDesc),
+    "font" if ext == "ttf" => Some(AssetChange::Font),
+    "music" | "sfx" => Some(AssetChange::Sound(file.to_string_lossy().into_owned())),
+    _ => None
+  }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_classify() {
+    let root = Path::new("/game");
+    assert_eq!(classify(root, Path::new("/game/img/chars/hero.png")), Some(AssetChange::Image(PathBuf::from("chars/hero.png"))));
+    assert_eq!(classify(root, Path::new("/game/img/hero.json")), Some(AssetChange::SpriteDesc));
+    assert_eq!(classify(root, Path::new("/game/conf/spritedesc.json")), Some(AssetChange::SpriteDesc));
+    assert_eq!(classify(root, Path::new("/game/conf/other.json")), None);
+    assert_eq!(classify(root, Path::new("/game/font/VcrOsdMono.ttf")), Some(AssetChange::Font));
+    assert_eq!(classify(root, Path::new("/game/sfx/jump.wav")), Some(AssetChange::Sound("jump.wav".to_owned())));
+    assert_eq!(classify(root, Path::new("/elsewhere/img/a.png")), None);
+  }
+
+  #[test]
+  fn test_poll() {
+    let root = crate::infraglobals::test_temp_dir("hotreload");
+    fs::create_dir_all(root.join("img")).unwrap();
+    fs::write(root.join("img").join("a.png"), b"1").unwrap();
+
+    let mut hr = HotReloader::new(&root);
+    assert!(hr.poll(1.0).is_empty());
+    fs::write(root.join("img").join("b.png"), b"2").unwrap(); // New file
+    assert!(hr.poll(0.1).is_empty()); // Not yet
+    assert_eq!(hr.poll(1.0), vec![AssetChange::Image(PathBuf::from("b.png"))]);
+    assert!(hr.poll(1.0).is_empty());
+    fs::remove_dir_all(&root).unwrap();
+  }
+}
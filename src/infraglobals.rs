@@ -74,12 +74,38 @@ pub fn get_logger_path() -> PathBuf {
   get_userdata_path().join("log")
 }
 
+// The player's settings (e.g. the input bindings)
+pub fn get_userconf_path() -> PathBuf {
+  get_userdata_path().join("conf")
+}
+
+pub fn get_input_map_path() -> PathBuf {
+  get_userconf_path().join("input_map.json")
+}
+
+// Input recordings
+pub fn get_replays_path() -> PathBuf {
+  get_userdata_path().join("replays")
+}
+
 
 #[cfg(test)] // TODO use crate ctor ? simpler in test context. 
 pub fn setup_test_folder() {
   set_install_path_once(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test").join("data"))
 }
 
+// An empty folder of its own, for a test writing files (the tests run in parallel, the processes too)
+#[cfg(test)]
+pub(crate) fn test_temp_dir(name: &str) -> PathBuf {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  static COUNT: AtomicUsize = AtomicUsize::new(0);
+  let dir = std::env::temp_dir()
+    .join(format!("hg_test_{}_{}_{}", name, std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed)));
+  let _ = std::fs::remove_dir_all(&dir);
+  std::fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
 /*
 #[cfg(test)]
 // Prec: setup_test_folder has been called.
@@ -7,7 +7,7 @@ use sdl2::ttf::Sdl2TtfContext;
 use sdl2::video::Window;
 use sdl2::{mixer, IntegerOrSdlError};
 use sdl2::Sdl;
-use sdl2::VideoSubsystem;
+use sdl2::{GameControllerSubsystem, VideoSubsystem};
 
 use winapi::shared::windef::DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2;
 use winapi::um::winuser::SetProcessDpiAwarenessContext;
@@ -27,6 +27,7 @@ pub(crate) fn init_sdl2(
      Sdl2ImageContext,
      Sdl2TtfContext,
      VideoSubsystem,
+     GameControllerSubsystem,
      Sdl2MixerContext,
      Canvas<Window>) 
 {
@@ -89,6 +90,11 @@ pub(crate) fn init_sdl2(
     errors::prompt_err_and_panic("SDL video init error", &e, None);
   });
 
+  // Gamepads (the controllers themselves are opened by HamGraph, when they are plugged)
+  let controller_subsystem = sdl_context.game_controller().unwrap_or_else(|e| {
+    errors::prompt_err_and_panic("SDL game controller init error", &e, None);
+  });
+
   let ttf_context = sdl2::ttf::init().unwrap_or_else(|e| {
     errors::prompt_err_and_panic(&format!("SDL ttf init error {e}"), "", None);
   });
@@ -132,6 +138,6 @@ pub(crate) fn init_sdl2(
       errors::prompt_err_and_panic("SDL initialization error", &e, None);
     });
 
-  (sdl_context, image_context, ttf_context, video_subsystem, mixer_subsystem, canvas)
+  (sdl_context, image_context, ttf_context, video_subsystem, controller_subsystem, mixer_subsystem, canvas)
   // no need to return the window, it is held by the canvas
 }
@@ -0,0 +1,290 @@
+use std::{collections::{BTreeMap, HashSet}, fs::File, io::{BufReader, BufWriter}, path::Path};
+
+use sdl2::{controller::{Axis, Button}, event::Event, keyboard::Keycode, mouse::MouseButton};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::infraglobals;
+
+const TRAINPUT: &str = "hg::input";
+
+// Beyond that, an axis counts as a pressed button
+const AXIS_THRESHOLD: i16 = 16_000;
+
+// A physical input. Names are the SDL ones ("Space", "Return", "A" for the keys, "a", "dpup",
+// "leftshoulder" for the gamepad buttons, "leftx", "righttrigger" for the axes) so that they are
+// readable in the JSON file, e.g.
+//   { "bindings": {
+//       "jump": [ { "Key": "Space" }, { "ControllerButton": "a" } ],
+//       "left": [ { "Key": "Left" }, { "ControllerAxis": { "axis": "leftx", "positive": false } } ] } }
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InputBinding {
+  Key(String),
+  MouseButton(String), // Left, Middle, Right, X1, X2
+  ControllerButton(String),
+  ControllerAxis { axis: String, positive: bool },
+}
+
+impl InputBinding {
+  // Same binding with the canonical SDL name (e.g. "space" -> "Space"), None if the name is unknown
+  pub fn normalized(&self) -> Option<InputBinding> {
+    match self {
+      InputBinding::Key(name) => Keycode::from_name(name).map(|k| InputBinding::Key(k.name())),
+      InputBinding::MouseButton(name) => mouse_button_from_name(name).map(|b| InputBinding::MouseButton(mouse_button_name(b).to_owned())),
+      InputBinding::ControllerButton(name) => Button::from_string(name).map(|b| InputBinding::ControllerButton(b.string())),
+      InputBinding::ControllerAxis { axis, positive } =>
+        Axis::from_string(axis).map(|a| InputBinding::ControllerAxis { axis: a.string(), positive: *positive }),
+    }
+  }
+}
+
+fn mouse_button_name(button: MouseButton) -> &'static str {
+  match button {
+    MouseButton::Left => "Left",
+    MouseButton::Middle => "Middle",
+    MouseButton::Right => "Right",
+    MouseButton::X1 => "X1",
+    MouseButton::X2 => "X2",
+    MouseButton::Unknown => "Unknown",
+  }
+}
+
+fn mouse_button_from_name(name: &str) -> Option<MouseButton> {
+  [MouseButton::Left, MouseButton::Middle, MouseButton::Right, MouseButton::X1, MouseButton::X2]
+    .into_iter()
+    .find(|b| mouse_button_name(*b).eq_ignore_ascii_case(name))
+}
+
+// Binds the physical inputs to named logical actions ("jump", "confirm"...).
+// The engine translates the SDL events into Action::Input { name, pressed }.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InputMap {
+  bindings: BTreeMap<String, Vec<InputBinding>>,
+  #[serde(skip)]
+  axes_pressed: HashSet<InputBinding>, // The axis directions currently beyond the threshold
+}
+
+// The bindings only, not the axes held right now
+impl PartialEq for InputMap {
+  fn eq(&self, other: &Self) -> bool {
+    self.bindings == other.bindings
+  }
+}
+
+impl InputMap {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Add a binding to an action (an action can have several bindings, and a binding several actions)
+  pub fn bind(&mut self, name: &str, binding: InputBinding) -> &mut Self {
+    match binding.normalized() {
+      Some(binding) => {
+        let bindings = self.bindings.entry(name.to_owned()).or_default();
+        if !bindings.contains(&binding) {
+          bindings.push(binding);
+        }
+      },
+      None => warn!(target: TRAINPUT, "Unknown input {:?}, not bound to '{}'", binding, name)
+    }
+    self
+  }
+
+  // Replace all the bindings of an action by that one (e.g. the player rebinds "jump")
+  pub fn rebind(&mut self, name: &str, binding: InputBinding) {
+    self.bindings.remove(name);
+    self.bind(name, binding);
+  }
+
+  pub fn unbind_all(&mut self, name: &str) {
+    self.bindings.remove(name);
+  }
+
+  pub fn bindings(&self, name: &str) -> &[InputBinding] {
+    self.bindings.get(name).map_or(&[], |b| b.as_slice())
+  }
+
+  // The bindings of the other map replace ours, action by action
+  pub fn overlay(&mut self, other: &InputMap) {
+    for (name, bindings) in &other.bindings {
+      self.bindings.insert(name.clone(), bindings.clone());
+    }
+  }
+
+  // The (action name, pressed) changes triggered by an SDL event. Key repeats are ignored.
+  pub fn translate(&mut self, event: &Event) -> Vec<(String, bool)> {
+    let changes: Vec<(InputBinding, bool)> = match event {
+      Event::KeyDown { keycode: Some(k), repeat: false, .. } => vec![(InputBinding::Key(k.name()), true)],
+      Event::KeyUp { keycode: Some(k), .. } => vec![(InputBinding::Key(k.name()), false)],
+      Event::MouseButtonDown { mouse_btn, .. } => vec![(InputBinding::MouseButton(mouse_button_name(*mouse_btn).to_owned()), true)],
+      Event::MouseButtonUp { mouse_btn, .. } => vec![(InputBinding::MouseButton(mouse_button_name(*mouse_btn).to_owned()), false)],
+      Event::ControllerButtonDown { button, .. } => vec![(InputBinding::ControllerButton(button.string()), true)],
+      Event::ControllerButtonUp { button, .. } => vec![(InputBinding::ControllerButton(button.string()), false)],
+      Event::ControllerAxisMotion { axis, value, .. } => self.axis_changes(&axis.string(), *value),
+      _ => return Vec::new()
+    };
+
+    let mut res = Vec::new();
+    for (input, pressed) in changes {
+      for (name, bindings) in &self.bindings {
+        if bindings.contains(&input) {
+          debug!(target: TRAINPUT, "Input '{}' pressed={} ({:?})", name, pressed, input);
+          res.push((name.clone(), pressed));
+        }
+      }
+    }
+    res
+  }
+
+  // An axis acts as two buttons (one per direction) : only report the threshold crossings
+  fn axis_changes(&mut self, axis: &str, value: i16) -> Vec<(InputBinding, bool)> {
+    let mut changes = Vec::new();
+    for (positive, active) in [(true, value > AXIS_THRESHOLD), (false, value < -AXIS_THRESHOLD)] {
+      let input = InputBinding::ControllerAxis { axis: axis.to_owned(), positive };
+      let was_active = self.axes_pressed.contains(&input);
+      if active && !was_active {
+        self.axes_pressed.insert(input.clone());
+        changes.push((input, true));
+      }
+      else if !active && was_active {
+        self.axes_pressed.remove(&input);
+        changes.push((input, false));
+      }
+    }
+    changes
+  }
+
+  pub fn load(path: &Path) -> Result<Self, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut map: InputMap = serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string())?;
+    // Normalize the names, dropping the unknown ones
+    for bindings in map.bindings.values_mut() {
+      *bindings = bindings.iter().filter_map(|b| b.normalized().or_else(|| {
+        warn!(target: TRAINPUT, "Unknown input {:?} in {:?}", b, path);
+        None
+      })).collect();
+    }
+    Ok(map)
+  }
+
+  pub fn save(&self, path: &Path) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+      std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(BufWriter::new(file), self).map_err(|e| e.to_string())
+  }
+
+  // The player's bindings, saved under the userdata path. The game gives its default bindings.
+  pub(crate) fn load_user(defaults: InputMap) -> Self {
+    let path = infraglobals::get_input_map_path();
+    let mut map = defaults;
+    if path.exists() {
+      match Self::load(&path) {
+        Ok(user_map) => {
+          info!(target: TRAINPUT, "Input bindings loaded from {:?}", path);
+          map.overlay(&user_map);
+        },
+        Err(e) => warn!(target: TRAINPUT, "Cannot load the input bindings {:?}: {}", path, e)
+      }
+    }
+    map
+  }
+
+  pub(crate) fn save_user(&self) {
+    let path = infraglobals::get_input_map_path();
+    match self.save(&path) {
+      Ok(()) => info!(target: TRAINPUT, "Input bindings saved to {:?}", path),
+      Err(e) => warn!(target: TRAINPUT, "Cannot save the input bindings {:?}: {}", path, e)
+    }
+  }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use sdl2::keyboard::Mod;
+
+  fn key_down(keycode: Keycode, repeat: bool) -> Event {
+    Event::KeyDown { timestamp: 0, window_id: 0, keycode: Some(keycode), scancode: None, keymod: Mod::NOMOD, repeat }
+  }
+
+  fn axis(axis: Axis, value: i16) -> Event {
+    Event::ControllerAxisMotion { timestamp: 0, which: 0, axis, value }
+  }
+
+  #[test]
+  fn test_translate() {
+    let mut map = InputMap::new();
+    map.bind("jump", InputBinding::Key("space".to_owned())) // Normalized to "Space"
+      .bind("jump", InputBinding::ControllerButton("a".to_owned()))
+      .bind("confirm", InputBinding::Key("Space".to_owned()))
+      .bind("fire", InputBinding::MouseButton("left".to_owned()))
+      .bind("nope", InputBinding::Key("NoSuchKey".to_owned()));
+    assert_eq!(map.bindings("jump").len(), 2);
+    assert!(map.bindings("nope").is_empty());
+
+    let mut pressed = map.translate(&key_down(Keycode::Space, false));
+    pressed.sort();
+    assert_eq!(pressed, vec![("confirm".to_owned(), true), ("jump".to_owned(), true)]);
+    assert!(map.translate(&key_down(Keycode::Space, true)).is_empty()); // Repeat
+    assert!(map.translate(&key_down(Keycode::Return, false)).is_empty());
+
+    let click = Event::MouseButtonUp { timestamp: 0, window_id: 0, which: 0, mouse_btn: MouseButton::Left, clicks: 1, x: 0, y: 0 };
+    assert_eq!(map.translate(&click), vec![("fire".to_owned(), false)]);
+
+    map.rebind("jump", InputBinding::Key("Up".to_owned()));
+    assert_eq!(map.bindings("jump"), &[InputBinding::Key("Up".to_owned())]);
+  }
+
+  #[test]
+  fn test_axis() {
+    let mut map = InputMap::new();
+    map.bind("left", InputBinding::ControllerAxis { axis: "leftx".to_owned(), positive: false });
+    map.bind("right", InputBinding::ControllerAxis { axis: "leftx".to_owned(), positive: true });
+
+    assert!(map.translate(&axis(Axis::LeftX, -2000)).is_empty()); // Dead zone
+    assert_eq!(map.translate(&axis(Axis::LeftX, -30000)), vec![("left".to_owned(), true)]);
+    assert!(map.translate(&axis(Axis::LeftX, -31000)).is_empty()); // Still pressed
+    // Straight from left to right
+    let mut changes = map.translate(&axis(Axis::LeftX, 30000));
+    changes.sort();
+    assert_eq!(changes, vec![("left".to_owned(), false), ("right".to_owned(), true)]);
+    assert!(map.translate(&axis(Axis::LeftY, 30000)).is_empty());
+
+    // "right" is held, still the same bindings
+    let mut same = InputMap::new();
+    same.bind("left", InputBinding::ControllerAxis { axis: "leftx".to_owned(), positive: false })
+      .bind("right", InputBinding::ControllerAxis { axis: "leftx".to_owned(), positive: true });
+    assert_eq!(map, same);
+  }
+
+  #[test]
+  fn test_json() {
+    let json = r#"{ "bindings": {
+      "jump": [ { "Key": "space" }, { "ControllerButton": "a" } ],
+      "left": [ { "ControllerAxis": { "axis": "leftx", "positive": false } }, { "Key": "NoSuchKey" } ]
+    } }"#;
+    let dir = infraglobals::test_temp_dir("input_map");
+    let path = dir.join("input_map.json");
+    std::fs::write(&path, json).unwrap();
+
+    let map = InputMap::load(&path).unwrap();
+    assert_eq!(map.bindings("jump"), &[InputBinding::Key("Space".to_owned()), InputBinding::ControllerButton("a".to_owned())]);
+    assert_eq!(map.bindings("left").len(), 1); // The unknown key was dropped
+
+    // Overlay : the saved bindings replace the defaults, action by action
+    let mut defaults = InputMap::new();
+    defaults.bind("jump", InputBinding::Key("Up".to_owned())).bind("fire", InputBinding::Key("X".to_owned()));
+    defaults.overlay(&map);
+    assert_eq!(defaults.bindings("jump").len(), 2);
+    assert_eq!(defaults.bindings("fire"), &[InputBinding::Key("X".to_owned())]);
+
+    defaults.save(&path).unwrap();
+    assert_eq!(InputMap::load(&path).unwrap(), defaults);
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
@@ -1,7 +1,7 @@
-use std::env;
+use std::{collections::HashMap, env};
 
 use sdl2::rect::Rect;
-use taffy::{prelude::{length, percent, TaffyMaxContent}, print_tree, FlexDirection, FlexWrap, NodeId, Style, TaffyTree};
+use taffy::{prelude::{auto, length, percent, TaffyMaxContent}, print_tree, FlexDirection, FlexWrap, NodeId, Style, TaffyTree};
 use tracing::{debug, info};
 
 use crate::scene::{SceneID, SceneStack};
@@ -210,6 +210,26 @@ impl LayoutManager {
     }
   }
 
+  // Take a node out of the flexbox flow, keeping its current place and size.
+  // E.g. a scene leaving during a transition must not share the space with the incoming one.
+  pub fn freeze_layout(&mut self, node_id: NodeId) {
+    let layout = *self.taffy_tree.layout(node_id).unwrap();
+    let mut style = self.taffy_tree.style(node_id).unwrap().clone();
+    style.position = taffy::Position::Absolute;
+    style.inset = taffy::Rect { 
+      left: length(layout.location.x), top: length(layout.location.y), right: auto(), bottom: auto() 
+    };
+    style.size = Size { width: length(layout.size.width), height: length(layout.size.height) };
+    let _ = self.taffy_tree.set_style(node_id, style);
+  }
+
+  // A hidden node (display: none) doesn't take any space in the flow
+  pub fn set_displayed(&mut self, node_id: NodeId, displayed: bool) {
+    let mut style = self.taffy_tree.style(node_id).unwrap().clone();
+    style.display = if displayed { taffy::Display::DEFAULT } else { taffy::Display::None };
+    let _ = self.taffy_tree.set_style(node_id, style);
+  }
+
   pub fn remove_layout(&mut self, node_id: NodeId) {
     self.taffy_tree.remove(node_id).unwrap();
   }
@@ -224,6 +244,18 @@ impl LayoutManager {
     ).unwrap();
   }
 
+  // Rank of every node in a depth-first traversal of the tree, i.e. the reading order of the UI
+  pub fn dfs_ranks(&self) -> HashMap<NodeId, usize> {
+    let mut ranks = HashMap::new();
+    let mut to_visit = vec![self.root_node_id];
+    while let Some(node) = to_visit.pop() {
+      ranks.insert(node, ranks.len());
+      let children = self.taffy_tree.children(node).unwrap_or_default();
+      to_visit.extend(children.into_iter().rev());
+    }
+    ranks
+  }
+
   pub fn get_style(&self, node_id: NodeId) -> &Style {
     self.taffy_tree.style(node_id).unwrap()
   }
@@ -12,6 +12,13 @@ pub mod layout_manager;
 pub mod font;
 pub mod renderer;
 pub mod egui_scene; 
+pub mod transition;
+pub mod input_map;
+pub mod gamepad;
+pub mod gesture;
+pub mod hit_shape;
+pub mod animation;
+pub mod vfs;
 
 pub mod button_scene; // temporary (TODO)
 pub mod text_scene;
@@ -19,7 +26,16 @@ pub mod text_scene;
 
 pub use hg::HamGraph;
 pub use hg::HamSdl2;
+pub use hg::ExitReason;
 pub use renderer::Renderer;
 
 mod utils;
+mod navigation;
+mod pointer;
+mod focus;
+mod replay;
+mod atlas;
+mod packer;
+mod preload;
+mod hot_reload;
 mod logger;
@@ -1,7 +1,7 @@
-use sdl2::mixer::{Music, Chunk};
+use sdl2::{mixer::{Chunk, LoaderRWops, Music}, rwops::RWops};
 use std::collections::HashMap;
 
-use crate::{errors::prompt_err_and_panic, infraglobals};
+use crate::{errors::prompt_err_and_panic, infraglobals, vfs};
 
 // TODO ! enum generation, sounddesc.json and so on.
 // But for now this'd be really useless
@@ -22,15 +22,23 @@ impl MixerManager<'_>
   }
 
   pub fn load_music(&mut self, name: &str) {
-    let music = Music::from_file(infraglobals::get_music_path().join(name))
-      .unwrap_or_else(|err| { prompt_err_and_panic("load_music failed", &err, None) });
+    let path = infraglobals::get_music_path().join(name);
+    let music = match vfs::read_static(&path) {
+      Some(bytes) => bytes.map_err(|e| e.to_string()).and_then(Music::from_static_bytes), // Streamed
+      None => Music::from_file(path)
+    }.unwrap_or_else(|err| { prompt_err_and_panic("load_music failed", &err, None) });
  
     self.music_store.insert(name.to_string(), music);
   }
 
   pub fn load_sfx(&mut self, name: &str) {
-    let chunk = Chunk::from_file(infraglobals::get_sfx_path().join(name))
-      .unwrap_or_else(|err| { prompt_err_and_panic("load_sfx failed", &err, None) });
+    let path = infraglobals::get_sfx_path().join(name);
+    let chunk = if vfs::in_archive(&path) {
+      vfs::read(&path).map_err(|e| e.to_string())
+        .and_then(|bytes| RWops::from_bytes(&bytes)?.load_wav()) // Decoded, the bytes can go
+    } else {
+      Chunk::from_file(path)
+    }.unwrap_or_else(|err| { prompt_err_and_panic("load_sfx failed", &err, None) });
     self.sfx_store.insert(name.to_string(), chunk);
   }
 
@@ -45,6 +53,12 @@ impl MixerManager<'_>
     }
   }
 
+  // The file changed on disk : loaded again on its next play (a music being played stops)
+  pub fn forget(&mut self, name: &str) {
+    self.music_store.remove(name);
+    self.sfx_store.remove(name);
+  }
+
   pub fn stop_music(&self) {
     Music::halt();
   }
@@ -0,0 +1,233 @@
+use std::collections::{HashMap, HashSet};
+use tracing::debug;
+
+use crate::scene::{SceneFlags, SceneID};
+
+const TRANAV: &str = "hg::nav";
+
+// "Go back" semantics on top of the SceneStack.
+// A host scene owns a navigation stack of screens, which are its children. Only the top screen
+// is shown : the ones below are kept alive (hidden, paused and input-disabled by the engine) so that
+// their state is still there when they are popped back.
+// This struct is only the bookkeeping, HamGraph applies the changes to the scene stack.
+
+struct NavStack {
+  host: SceneID,
+  entries: Vec<SceneID>, // bottom (root) to top
+}
+
+pub(crate) struct Navigator {
+  stacks: Vec<NavStack>, // The most recently used stack is the last one
+  saved_focus: HashMap<SceneID, SceneID>, // Buried entry -> the scene which had the focus then
+  buried_flags: HashMap<SceneID, SceneFlags>, // Buried entry -> the flags the navigation set (not the game's)
+}
+
+impl Navigator {
+  pub(crate) fn new() -> Self {
+    Self { stacks: Vec::new(), saved_focus: HashMap::new(), buried_flags: HashMap::new() }
+  }
+
+  // The host of the navigation stack a scene belongs to.
+  // A scene which is not a navigation entry hosts its own stack.
+  pub(crate) fn host_of(&self, scene: SceneID) -> SceneID {
+    self.stacks.iter()
+      .find(|st| st.entries.contains(&scene))
+      .map_or(scene, |st| st.host)
+  }
+
+  #[cfg(test)]
+  pub(crate) fn top(&self, host: SceneID) -> Option<SceneID> {
+    self.stacks.iter().find(|st| st.host == host).and_then(|st| st.entries.last().copied())
+  }
+
+  // Returns the previous top, to be hidden. focused : the focused scene, given back when the
+  // previous top is revealed again (cf. take_focus)
+  pub(crate) fn push(&mut self, host: SceneID, id: SceneID, focused: Option<SceneID>) -> Option<SceneID> {
+    let stack = self.use_stack(host);
+    let prev = stack.entries.last().copied();
+    stack.entries.push(id);
+    debug!(target: TRANAV, "Push {} on host {} (depth {})", id, host, stack.entries.len());
+    if let (Some(prev), Some(focused)) = (prev, focused) {
+      self.saved_focus.insert(prev, focused);
+    }
+    prev
+  }
+
+  // An entry is buried : the flags the navigation added, the only ones to clear when it is revealed
+  pub(crate) fn set_buried_flags(&mut self, entry: SceneID, flags: SceneFlags) {
+    self.buried_flags.insert(entry, flags);
+  }
+
+  pub(crate) fn take_buried_flags(&mut self, entry: SceneID) -> SceneFlags {
+    self.buried_flags.remove(&entry).unwrap_or(SceneFlags::empty())
+  }
+
+  // A revealed entry : the scene which had the focus when it was buried
+  pub(crate) fn take_focus(&mut self, entry: SceneID) -> Option<SceneID> {
+    self.saved_focus.remove(&entry)
+  }
+
+  // Returns (popped scene, revealed scene). The root entry is never popped.
+  pub(crate) fn pop(&mut self, host: SceneID) -> Option<(SceneID, SceneID)> {
+    let stack = self.use_stack(host);
+    if stack.entries.len() < 2 {
+      return None;
+    }
+    let popped = stack.entries.pop().unwrap();
+    debug!(target: TRANAV, "Pop {} from host {} (depth {})", popped, host, stack.entries.len());
+    Some((popped, *stack.entries.last().unwrap()))
+  }
+
+  // Returns the replaced top, if any
+  pub(crate) fn replace(&mut self, host: SceneID, id: SceneID) -> Option<SceneID> {
+    let stack = self.use_stack(host);
+    let old = stack.entries.pop();
+    stack.entries.push(id);
+    old
+  }
+
+  // Returns (popped scenes from top to bottom, revealed root)
+  pub(crate) fn pop_to_root(&mut self, host: SceneID) -> Option<(Vec<SceneID>, SceneID)> {
+    let stack = self.use_stack(host);
+    if stack.entries.len() < 2 {
+      return None;
+    }
+    let popped = stack.entries.drain(1..).rev().collect();
+    Some((popped, stack.entries[0]))
+  }
+
+  // The host which the back button (Escape, gamepad B) applies to :
+  // the most recently used stack that has something to pop.
+  pub(crate) fn back_target(&self) -> Option<SceneID> {
+    self.stacks.iter().rev().find(|st| st.entries.len() > 1).map(|st| st.host)
+  }
+
+  // Scenes were closed (by the navigation or by anything else) : clean up the stacks.
+  // Returns the entries which became the top of their stack, to be shown again.
+  pub(crate) fn forget(&mut self, removed: &HashSet<SceneID>) -> Vec<SceneID> {
+    self.saved_focus.retain(|entry, focused| !removed.contains(entry) && !removed.contains(focused));
+    self.buried_flags.retain(|entry, _| !removed.contains(entry));
+    let mut revealed = Vec::new();
+    self.stacks.retain_mut(|st| {
+      if removed.contains(&st.host) {
+        return false;
+      }
+      let old_top = st.entries.last().copied();
+      st.entries.retain(|id| !removed.contains(id));
+      let new_top = st.entries.last().copied();
+      if new_top != old_top {
+        revealed.extend(new_top);
+      }
+      !st.entries.is_empty()
+    });
+    revealed
+  }
+
+  // Get (or create) the stack of a host and make it the most recently used
+  fn use_stack(&mut self, host: SceneID) -> &mut NavStack {
+    let stack = match self.stacks.iter().position(|st| st.host == host) {
+      Some(pos) => self.stacks.remove(pos),
+      None => NavStack { host, entries: Vec::new() }
+    };
+    self.stacks.push(stack);
+    self.stacks.last_mut().unwrap()
+  }
+}
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_push_pop() {
+    let mut nav = Navigator::new();
+    assert_eq!(nav.push(1, 2, None), None);
+    assert_eq!(nav.push(1, 3, None), Some(2));
+    assert_eq!(nav.push(1, 4, None), Some(3));
+    assert_eq!(nav.host_of(3), 1);
+    assert_eq!(nav.host_of(1), 1); // Not an entry : hosts its own stack
+    assert_eq!(nav.top(1), Some(4));
+
+    assert_eq!(nav.pop(1), Some((4, 3)));
+    assert_eq!(nav.pop(1), Some((3, 2)));
+    assert_eq!(nav.pop(1), None); // The root stays
+    assert_eq!(nav.top(1), Some(2));
+  }
+
+  #[test]
+  fn test_replace_and_pop_to_root() {
+    let mut nav = Navigator::new();
+    nav.push(1, 2, None);
+    nav.push(1, 3, None);
+    assert_eq!(nav.replace(1, 4), Some(3));
+    assert_eq!(nav.top(1), Some(4));
+    nav.push(1, 5, None);
+    assert_eq!(nav.pop_to_root(1), Some((vec![5, 4], 2)));
+    assert_eq!(nav.pop_to_root(1), None);
+  }
+
+  #[test]
+  fn test_back_target() {
+    let mut nav = Navigator::new();
+    assert_eq!(nav.back_target(), None);
+    nav.push(1, 2, None);
+    assert_eq!(nav.back_target(), None); // Nothing to pop
+    nav.push(1, 3, None);
+    nav.push(10, 11, None);
+    nav.push(10, 12, None);
+    assert_eq!(nav.back_target(), Some(10)); // Most recently used
+    nav.pop(10);
+    assert_eq!(nav.back_target(), Some(1));
+  }
+
+  #[test]
+  fn test_forget() {
+    let mut nav = Navigator::new();
+    nav.push(1, 2, None);
+    nav.push(1, 3, None);
+    nav.push(10, 11, None);
+
+    // The top of host 1 was closed from outside
+    assert_eq!(nav.forget(&HashSet::from([3])), vec![2]);
+    assert_eq!(nav.top(1), Some(2));
+    // A buried entry : nothing to reveal
+    nav.push(1, 4, None);
+    assert!(nav.forget(&HashSet::from([2])).is_empty());
+    // Host closed : the stack is dropped
+    assert!(nav.forget(&HashSet::from([10, 11])).is_empty());
+    assert_eq!(nav.top(10), None);
+    assert_eq!(nav.top(1), Some(4));
+  }
+
+  #[test]
+  fn test_saved_focus() {
+    let mut nav = Navigator::new();
+    nav.push(1, 2, None);
+    nav.push(1, 3, Some(20)); // 20 (a button of 2) had the focus
+    nav.push(1, 4, Some(30));
+    assert_eq!(nav.pop(1), Some((4, 3)));
+    assert_eq!(nav.take_focus(3), Some(30));
+    assert_eq!(nav.take_focus(3), None);
+
+    // The focused scene died in the meantime
+    assert_eq!(nav.forget(&HashSet::from([20])), Vec::<SceneID>::new());
+    assert_eq!(nav.pop(1), Some((3, 2)));
+    assert_eq!(nav.take_focus(2), None);
+  }
+
+  #[test]
+  fn test_buried_flags() {
+    let mut nav = Navigator::new();
+    nav.push(1, 2, None);
+    nav.push(1, 3, None);
+    nav.set_buried_flags(2, SceneFlags::Hidden); // The game had paused 2 itself
+    assert_eq!(nav.take_buried_flags(2), SceneFlags::Hidden);
+    assert_eq!(nav.take_buried_flags(2), SceneFlags::empty());
+
+    nav.set_buried_flags(2, SceneFlags::Paused);
+    nav.forget(&HashSet::from([2]));
+    assert_eq!(nav.take_buried_flags(2), SceneFlags::empty());
+  }
+}
@@ -0,0 +1,157 @@
+// Runtime atlases : the small surfaces (the texts, the loose icons) share a few big textures, the pages,
+// instead of one texture each. Shelf packing, a page is reused once all its surfaces are freed.
+use sdl2::rect::Rect;
+
+pub(crate) const PAGE_SIZE: u32 = 1024;
+pub(crate) const PADDING: u32 = 1; // Between the surfaces, against the bleeding when scaled
+
+struct Shelf {
+  y: u32,
+  height: u32,
+  used_width: u32,
+}
+
+pub(crate) struct ShelfPacker {
+  width: u32,
+  height: u32,
+  shelves: Vec<Shelf>,
+  live: usize, // Surfaces placed and not released
+}
+
+impl ShelfPacker {
+  pub(crate) fn new(width: u32, height: u32) -> Self {
+    Self { width, height, shelves: Vec::new(), live: 0 }
+  }
+
+  // The tightest shelf with room, else a new shelf below the others
+  pub(crate) fn pack(&mut self, w: u32, h: u32) -> Option<Rect> {
+    let (pw, ph) = (w + PADDING, h + PADDING);
+    let width = self.width;
+    let best = self.shelves.iter_mut()
+      .filter(|s| s.height >= ph && width - s.used_width >= pw)
+      .min_by_key(|s| s.height);
+    let shelf = match best {
+      Some(shelf) => shelf,
+      None => {
+        let y = self.shelves.last().map_or(0, |s| s.y + s.height);
+        if pw > self.width || y + ph > self.height {
+          return None;
+        }
+        self.shelves.push(Shelf { y, height: ph, used_width: 0 });
+        self.shelves.last_mut().unwrap()
+      }
+    };
+    let rect = Rect::new(shelf.used_width as i32, shelf.y as i32, w, h);
+    shelf.used_width += pw;
+    self.live += 1;
+    Some(rect)
+  }
+
+  // The space is only given back when the page gets empty. Returns true then.
+  pub(crate) fn release(&mut self) -> bool {
+    self.live = self.live.saturating_sub(1);
+    if self.live == 0 {
+      self.shelves.clear();
+      return true;
+    }
+    false
+  }
+}
+
+// The pages, by texture id
+pub(crate) struct RuntimeAtlas {
+  pages: Vec<(usize, ShelfPacker)>,
+}
+
+impl RuntimeAtlas {
+  pub(crate) fn new() -> Self {
+    Self { pages: Vec::new() }
+  }
+
+  // The bigger ones keep their own texture
+  pub(crate) fn accepts(w: u32, h: u32) -> bool {
+    w > 0 && h > 0 && w <= PAGE_SIZE / 2 && h <= PAGE_SIZE / 4
+  }
+
+  // Where to copy a w x h surface : (texture id, rect). new_page : creates the texture of a new page.
+  pub(crate) fn place(&mut self, w: u32, h: u32, new_page: impl FnOnce() -> Option<usize>) -> Option<(usize, Rect)> {
+    if !Self::accepts(w, h) {
+      return None;
+    }
+    for (texture_id, packer) in &mut self.pages {
+      if let Some(rect) = packer.pack(w, h) {
+        return Some((*texture_id, rect));
+      }
+    }
+    let texture_id = new_page()?;
+    let mut packer = ShelfPacker::new(PAGE_SIZE, PAGE_SIZE);
+    let rect = packer.pack(w, h)?;
+    self.pages.push((texture_id, packer));
+    Some((texture_id, rect))
+  }
+
+  pub(crate) fn is_page(&self, texture_id: usize) -> bool {
+    self.pages.iter().any(|(id, _)| *id == texture_id)
+  }
+
+  // A surface of that page is not used anymore
+  pub(crate) fn release(&mut self, texture_id: usize) {
+    if let Some((_, packer)) = self.pages.iter_mut().find(|(id, _)| *id == texture_id) {
+      packer.release();
+    }
+  }
+
+  pub(crate) fn page_count(&self) -> usize {
+    self.pages.len()
+  }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_shelf_packer() {
+    let mut packer = ShelfPacker::new(100, 50);
+    assert_eq!(packer.pack(40, 20), Some(Rect::new(0, 0, 40, 20)));
+    assert_eq!(packer.pack(40, 10), Some(Rect::new(41, 0, 40, 10))); // Same shelf
+    assert_eq!(packer.pack(30, 10), Some(Rect::new(0, 21, 30, 10))); // A tighter shelf is opened
+    assert_eq!(packer.pack(30, 10), Some(Rect::new(31, 21, 30, 10)));
+    assert_eq!(packer.pack(10, 30), None); // No room below
+    assert_eq!(packer.pack(101, 1), None);
+
+    for _ in 0..3 {
+      assert!(!packer.release());
+    }
+    assert!(packer.release());
+    assert_eq!(packer.pack(10, 30), Some(Rect::new(0, 0, 10, 30))); // Empty again
+  }
+
+  #[test]
+  fn test_runtime_atlas() {
+    let mut atlas = RuntimeAtlas::new();
+    let mut next_texture = 7;
+    let mut new_page = || { next_texture += 1; Some(next_texture) };
+
+    assert_eq!(atlas.place(600, 10, &mut new_page), None); // Too wide
+    let (page, rect) = atlas.place(300, 200, &mut new_page).unwrap();
+    assert_eq!((page, rect), (8, Rect::new(0, 0, 300, 200)));
+    assert!(atlas.is_page(8) && !atlas.is_page(7));
+
+    // 6 more on the first page, 8 on the next ones
+    let mut pages = Vec::new();
+    for _ in 0..20 {
+      pages.push(atlas.place(500, 250, &mut new_page).unwrap().0);
+    }
+    assert_eq!(atlas.page_count(), 3);
+    assert_eq!((pages[5], pages[6], pages[13], pages[14], pages[19]), (8, 9, 9, 10, 10));
+
+    for _ in 0..7 {
+      atlas.release(8);
+    }
+    assert_eq!(atlas.place(500, 250, || None), Some((8, Rect::new(0, 0, 500, 250)))); // Reused from the top
+  }
+}
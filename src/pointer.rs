@@ -0,0 +1,157 @@
+use sdl2::{event::{Event, WindowEvent}, mouse::MouseButton};
+use tracing::debug;
+
+use crate::{action::{Action, DragPayload, EventKind}, action_bus::ActionBus, scene::{SceneID, SceneStack}};
+
+const TRAPTR: &str = "hg::pointer";
+const TOUCH_MOUSEID: u32 = u32::MAX; // SDL_TOUCH_MOUSEID : the mouse events SDL makes up from the touches
+
+// The touches already go to the scenes as touches, not twice
+pub(crate) fn is_touch_mouse(event: &Event) -> bool {
+  matches!(event, Event::MouseMotion { which: TOUCH_MOUSEID, .. } 
+    | Event::MouseButtonDown { which: TOUCH_MOUSEID, .. } | Event::MouseButtonUp { which: TOUCH_MOUSEID, .. })
+}
+
+struct Drag {
+  source: SceneID,
+  payload: DragPayload,
+  target: Option<SceneID>, // The drop target under the pointer
+}
+
+// Keeps track of the mouse pointer and of the scene under it (hovering).
+// Also the pointer capture : the scene which handled a mouse press gets all the motion events and 
+// the release, wherever the pointer goes. While it holds the capture it may start a drag.
+pub(crate) struct PointerTracker {
+  pos: Option<(i32, i32)>, // None when the pointer is out of the window
+  moved: bool, // since the last update
+  hovered: Option<SceneID>,
+  capture: Option<(SceneID, MouseButton)>,
+  drag: Option<Drag>,
+}
+
+impl PointerTracker {
+  pub(crate) fn new() -> Self {
+    Self { pos: None, moved: false, hovered: None, capture: None, drag: None }
+  }
+
+  // Fed with every SDL event, before it is dispatched
+  pub(crate) fn handle_event(&mut self, event: &Event) {
+    match event {
+      Event::MouseMotion { x, y, .. } => {
+        self.pos = Some((*x, *y));
+        self.moved = true;
+      },
+      Event::MouseButtonDown { x, y, .. } | Event::MouseButtonUp { x, y, .. } => {
+        self.pos = Some((*x, *y));
+      },
+      Event::Window { win_event: WindowEvent::Leave, .. } => {
+        self.pos = None;
+      },
+      _ => {}
+    }
+  }
+
+  pub(crate) fn captor(&self) -> Option<SceneID> {
+    self.capture.map(|(id, _)| id)
+  }
+
+  // Called when a scene handled a mouse press. The first press wins : the capture lasts until that 
+  // button is released.
+  pub(crate) fn capture(&mut self, id: SceneID, button: MouseButton) {
+    if self.capture.is_none() {
+      debug!(target: TRAPTR, "Pointer captured by {} ({:?})", id, button);
+      self.capture = Some((id, button));
+    }
+  }
+
+  pub(crate) fn is_capture_button(&self, button: MouseButton) -> bool {
+    self.capture.is_some_and(|(_, b)| b == button)
+  }
+
+  // Action::StartDrag. Only the captor may start a drag. Returns false if the request was ignored.
+  pub(crate) fn start_drag(&mut self, source: SceneID, payload: DragPayload, scene_stack: &mut SceneStack, action_bus: &mut ActionBus) -> bool {
+    if self.captor() != Some(source) || self.drag.is_some() {
+      return false;
+    }
+    debug!(target: TRAPTR, "Drag of a '{}' started by {}", payload.kind, source);
+    self.drag = Some(Drag { source, payload, target: None });
+    if let Some((x, y)) = self.pos {
+      self.drag_over(x, y, scene_stack, action_bus);
+    }
+    true
+  }
+
+  // The pointer moved while dragging : update the drop target
+  pub(crate) fn drag_over(&mut self, x: i32, y: i32, scene_stack: &mut SceneStack, action_bus: &mut ActionBus) {
+    let Some(drag) = self.drag.as_mut() else { return; };
+    let source = drag.source;
+    let target = scene_stack.drop_target_at(x, y, &drag.payload, source);
+    if target != drag.target {
+      if let Some(old) = drag.target {
+        scene_stack.send_to(old, &Action::DragLeave { source }, Some(source), action_bus);
+      }
+      if let Some(new) = target {
+        scene_stack.send_to(new, &Action::DragEnter { source }, Some(source), action_bus);
+      }
+      drag.target = target;
+    }
+    if let Some(id) = target {
+      scene_stack.send_to(id, &Action::DragOver { source, x, y }, Some(source), action_bus);
+    }
+  }
+
+  // The capture button was released (after the captor got the event) : drop, if dragging.
+  pub(crate) fn release(&mut self, x: i32, y: i32, scene_stack: &mut SceneStack, action_bus: &mut ActionBus) {
+    debug!(target: TRAPTR, "Pointer released by {:?}", self.captor());
+    self.capture = None;
+    let Some(drag) = self.drag.take() else { return; };
+    let source = drag.source;
+    // The target under the release point, should the pointer have jumped
+    let target = scene_stack.drop_target_at(x, y, &drag.payload, source);
+    if let Some(old) = drag.target.filter(|id| Some(*id) != target) {
+      scene_stack.send_to(old, &Action::DragLeave { source }, Some(source), action_bus);
+    }
+    let accepted = match target {
+      Some(id) => scene_stack.send_to(id, &Action::Drop { source, payload: drag.payload, x, y }, Some(source), action_bus),
+      None => false
+    };
+    debug!(target: TRAPTR, "Dropped on {:?}, accepted: {}", target, accepted);
+    scene_stack.send_to(source, &Action::DragEnd { target, accepted }, None, action_bus);
+  }
+
+  // Called once per frame, after the layout update : the scenes may also move under a still pointer.
+  pub(crate) fn update(&mut self, scene_stack: &mut SceneStack, action_bus: &mut ActionBus) {
+    // The captor or the drop target may have been closed
+    if self.captor().is_some_and(|id| !scene_stack.contains(id)) {
+      debug!(target: TRAPTR, "Captor closed, capture and drag cancelled");
+      self.capture = None;
+      let drag = self.drag.take();
+      if let Some((source, target)) = drag.and_then(|d| d.target.map(|t| (d.source, t))).filter(|(_, t)| scene_stack.contains(*t)) {
+        scene_stack.send_to(target, &Action::DragLeave { source }, None, action_bus);
+      }
+    }
+    if let Some(drag) = self.drag.as_mut().filter(|d| d.target.is_some_and(|id| !scene_stack.contains(id))) {
+      drag.target = None;
+    }
+
+    let now_hovered = self.pos.and_then(|(x, y)| scene_stack.scene_at(x, y, &EventKind::Pointer));
+
+    if now_hovered != self.hovered {
+      debug!(target: TRAPTR, "Hovered scene {:?} -> {:?}", self.hovered, now_hovered);
+      if let Some(old) = self.hovered {
+        scene_stack.send_to(old, &Action::PointerLeave, None, action_bus);
+      }
+      if let Some(new) = now_hovered {
+        scene_stack.send_to(new, &Action::PointerEnter, None, action_bus);
+      }
+      self.hovered = now_hovered;
+    }
+
+    if self.moved {
+      if let (Some(id), Some((x, y))) = (self.hovered, self.pos) {
+        scene_stack.send_to(id, &Action::PointerMove { x, y }, None, action_bus);
+      }
+      self.moved = false;
+    }
+  }
+}
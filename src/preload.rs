@@ -0,0 +1,143 @@
+use std::{path::{Path, PathBuf}, sync::mpsc::{self, Receiver, TryRecvError}, thread, time::Duration};
+
+use sdl2::pixels::PixelFormatEnum;
+use tracing::{debug, warn};
+
+use crate::{scene::SceneID, vfs};
+
+const TRAPRELOAD: &str = "hg::preload";
+
+// Time spent uploading the decoded images to textures, per frame (at least one image per frame)
+pub(crate) const UPLOAD_BUDGET: Duration = Duration::from_millis(4);
+
+// An image decoded by the worker, ABGR8888 (R, G, B, A in memory).
+// The SDL surfaces cannot leave their thread, the bytes can.
+pub(crate) struct DecodedImage {
+  pub(crate) width: u32,
+  pub(crate) height: u32,
+  pub(crate) pitch: usize,
+  pub(crate) pixels: Vec<u8>,
+}
+
+impl DecodedImage {
+  pub(crate) fn decode(path: &Path) -> Result<Self, String> {
+    let surface = vfs::load_surface(path)?.convert_format(PixelFormatEnum::ABGR8888)?;
+    let pixels = surface.without_lock().ok_or("Surface must be locked")?.to_vec();
+    Ok(Self { width: surface.width(), height: surface.height(), pitch: surface.pitch() as usize, pixels })
+  }
+}
+
+type Decoded = (usize, Result<DecodedImage, String>);
+
+// Action::Preload : a worker thread decodes the sheets, the main thread uploads them
+pub(crate) struct PreloadJob {
+  pub(crate) owner: SceneID, // Gets PreloadProgress and PreloadDone
+  rx: Receiver<Decoded>,
+  pub(crate) total: usize,
+  pub(crate) loaded: usize,
+}
+
+impl PreloadJob {
+  // files : the texture ids and image paths still to load. already : the ones already loaded
+  pub(crate) fn start(owner: SceneID, files: Vec<(usize, PathBuf)>, already: usize) -> Self {
+    Self::start_with(owner, files, already, DecodedImage::decode)
+  }
+
+  fn start_with(owner: SceneID, files: Vec<(usize, PathBuf)>, already: usize,
+                decode: fn(&Path) -> Result<DecodedImage, String>) -> Self {
+    let (tx, rx) = mpsc::channel();
+    let total = files.len() + already;
+    debug!(target: TRAPRELOAD, "Preloading {} textures for scene {} ({} already loaded)", files.len(), owner, already);
+    if !files.is_empty() {
+      thread::spawn(move || {
+        for (texture_id, path) in files {
+          if tx.send((texture_id, decode(&path))).is_err() {
+            break; // Job dropped
+          }
+        }
+      });
+    }
+    Self { owner, rx, total, loaded: already }
+  }
+
+  // The next decoded image, if ready. A failed one is counted as loaded anyway.
+  pub(crate) fn next_decoded(&mut self) -> Option<(usize, DecodedImage)> {
+    loop {
+      let (texture_id, res) = match self.rx.try_recv() {
+        Ok(decoded) => decoded,
+        Err(TryRecvError::Empty) => return None,
+        Err(TryRecvError::Disconnected) => {
+          // The worker died (panicked) : the job is over, with what was loaded
+          if self.loaded < self.total {
+            warn!(target: TRAPRELOAD, "Preload worker of scene {} stopped, {} textures not preloaded", self.owner, self.total - self.loaded);
+            self.loaded = self.total;
+          }
+          return None;
+        }
+      };
+      self.loaded += 1;
+      match res {
+        Ok(image) => return Some((texture_id, image)),
+        Err(e) => warn!(target: TRAPRELOAD, "Cannot preload texture {}: {}", texture_id, e)
+      }
+    }
+  }
+
+  pub(crate) fn is_done(&self) -> bool {
+    self.loaded >= self.total
+  }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fake_decode(path: &Path) -> Result<DecodedImage, String> {
+    if path.ends_with("broken.png") {
+      return Err("broken".to_owned());
+    }
+    Ok(DecodedImage { width: 1, height: 1, pitch: 4, pixels: vec![0; 4] })
+  }
+
+  fn panicking_decode(_path: &Path) -> Result<DecodedImage, String> {
+    panic!("decoder crashed");
+  }
+
+  #[test]
+  fn test_preload_worker_died() {
+    let mut job = PreloadJob::start_with(1, vec![(3, PathBuf::from("a.png"))], 0, panicking_decode);
+    for _ in 0..100 {
+      if job.next_decoded().is_none() && job.is_done() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(5));
+    }
+    assert!(job.is_done());
+  }
+
+  #[test]
+  fn test_preload_job() {
+    let files = vec![(3, PathBuf::from("a.png")), (5, PathBuf::from("broken.png")), (7, PathBuf::from("b.png"))];
+    let mut job = PreloadJob::start_with(1, files, 2, fake_decode);
+    assert_eq!((job.total, job.loaded), (5, 2));
+
+    let mut uploaded = Vec::new();
+    for _ in 0..100 {
+      while let Some((texture_id, _)) = job.next_decoded() {
+        uploaded.push(texture_id);
+      }
+      if job.is_done() {
+        break;
+      }
+      thread::sleep(Duration::from_millis(5));
+    }
+    assert!(job.is_done());
+    assert_eq!(uploaded, vec![3, 7]); // The broken one is skipped
+
+    let empty = PreloadJob::start_with(1, Vec::new(), 0, fake_decode);
+    assert!(empty.is_done());
+  }
+}
@@ -1,9 +1,10 @@
 use egui::{epaint, Context};
 use egui_sdl2_canvas::Painter;
 use egui_sdl2_platform::Platform;
-use sdl2::{pixels::Color, render::{Canvas, TextureCreator}, video::{Window, WindowContext}, Sdl, VideoSubsystem};
+use sdl2::{pixels::Color, rect::Rect, render::{Canvas, Texture, TextureCreator}, video::{Window, WindowContext}, Sdl, VideoSubsystem};
+use tracing::warn;
 
-use crate::{font::FontStore, sprite::SpriteStore};
+use crate::{font::FontStore, sprite::{DrawParams, SpriteHandle, SpriteStore}};
 
 // experiment
 pub fn temp_change_font_size(ctx: &egui::Context) {
@@ -59,8 +60,25 @@ impl<'a> Renderer<'a> {
     return &self.egui_ctx.as_ref().unwrap();
   }
 
-  pub fn render_sprite(&mut self, sprite_id: usize, pos_x: i32, pos_y: i32, alpha: Option<u8>) {
-    self.sprite_store.render(self.canvas, sprite_id, pos_x, pos_y, alpha);
+  // params : a DrawParams, or just an Option<u8> alpha
+  pub fn render_sprite(&mut self, sprite: impl Into<SpriteHandle>, pos_x: i32, pos_y: i32, params: impl Into<DrawParams>) {
+    self.sprite_store.render(self.canvas, sprite.into(), pos_x, pos_y, &params.into());
+  }
+
+  // Fills dest with a nine-slice sprite (e.g. a panel following its layout rect)
+  pub fn render_nine_slice(&mut self, sprite: impl Into<SpriteHandle>, dest: Rect, params: impl Into<DrawParams>) {
+    self.sprite_store.render_nine_slice(self.canvas, sprite.into(), dest, &params.into());
+  }
+
+  // Redirect the rendering to a target texture (None : back to the window).
+  // The sdl2 crate only offers with_texture_canvas(), which borrows the canvas in a closure,
+  // that we can't do while the scenes render through self. So we call SDL directly.
+  pub(crate) fn set_render_target(&mut self, texture: Option<&Texture>) {
+    let raw_tex = texture.map_or(std::ptr::null_mut(), |t| t.raw());
+    let ret = unsafe { sdl2::sys::SDL_SetRenderTarget(self.canvas.raw(), raw_tex) };
+    if ret != 0 {
+      warn!(target: "hg::render", "SDL_SetRenderTarget failed: {}", sdl2::get_error());
+    }
   }
 
   pub fn end_egui_pass_and_paint(&mut self) {
@@ -0,0 +1,294 @@
+use std::{fs::File, io::{BufRead, BufReader, BufWriter, Lines, Write}, path::{Path, PathBuf}};
+
+use sdl2::{controller::{Axis, Button}, event::{Event, WindowEvent}, keyboard::{Keycode, Mod, Scancode}, mouse::{MouseButton, MouseState, MouseWheelDirection}};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{action::Action, gamepad::GamepadInfo, infraglobals};
+
+const TRAREPLAY: &str = "hg::replay";
+
+// Input recording and replay.
+// While recording, every SDL event given to the main loop is written to a JSON-lines file
+// (one line per frame, with the frame number and its delta time) in the userdata replays folder.
+// A replay feeds these events back to the main loop instead of the event pump, with the recorded
+// delta times : the scenes see exactly the same thing.
+// The timestamps are not recorded (always 0 when replayed).
+// The gamepads hot-plug is recorded as the scenes saw it (GamepadConnected / GamepadDisconnected) : 
+// a replay doesn't open the pads again, they may not be connected then.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedFinger {
+  touch_id: i64,
+  finger_id: i64,
+  x: f32,
+  y: f32,
+  dx: f32,
+  dy: f32,
+  pressure: f32,
+}
+
+// The SDL events the engine dispatches, in a serializable form
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RecordedEvent {
+  Quit,
+  KeyDown { window_id: u32, keycode: Option<i32>, scancode: Option<i32>, keymod: u16, repeat: bool },
+  KeyUp { window_id: u32, keycode: Option<i32>, scancode: Option<i32>, keymod: u16, repeat: bool },
+  TextInput { window_id: u32, text: String },
+  TextEditing { window_id: u32, text: String, start: i32, length: i32 },
+  MouseMotion { window_id: u32, which: u32, state: u32, x: i32, y: i32, xrel: i32, yrel: i32 },
+  MouseButtonDown { window_id: u32, which: u32, button: u8, clicks: u8, x: i32, y: i32 },
+  MouseButtonUp { window_id: u32, which: u32, button: u8, clicks: u8, x: i32, y: i32 },
+  MouseWheel { window_id: u32, which: u32, x: i32, y: i32, direction: u32, precise_x: f32, precise_y: f32, mouse_x: i32, mouse_y: i32 },
+  WindowResized { window_id: u32, w: i32, h: i32 },
+  WindowFocusGained { window_id: u32 },
+  WindowFocusLost { window_id: u32 },
+  WindowEnter { window_id: u32 },
+  WindowLeave { window_id: u32 },
+  ControllerButtonDown { which: u32, button: String },
+  ControllerButtonUp { which: u32, button: String },
+  ControllerAxisMotion { which: u32, axis: String, value: i16 },
+  GamepadConnected(GamepadInfo), // Not SDL events, cf. Recorder::record_hotplug
+  GamepadDisconnected { id: u32 },
+  FingerDown(RecordedFinger),
+  FingerMotion(RecordedFinger),
+  FingerUp(RecordedFinger),
+  MultiGesture { touch_id: i64, d_theta: f32, d_dist: f32, x: f32, y: f32, num_fingers: u16 },
+}
+
+impl RecordedEvent {
+  // None for the events the engine doesn't dispatch
+  pub fn from_event(event: &Event) -> Option<Self> {
+    let rec = match event.clone() {
+      Event::Quit { .. } => RecordedEvent::Quit,
+      Event::KeyDown { window_id, keycode, scancode, keymod, repeat, .. } => RecordedEvent::KeyDown {
+        window_id, keycode: keycode.map(|k| k.into_i32()), scancode: scancode.map(|s| s as i32), keymod: keymod.bits(), repeat },
+      Event::KeyUp { window_id, keycode, scancode, keymod, repeat, .. } => RecordedEvent::KeyUp {
+        window_id, keycode: keycode.map(|k| k.into_i32()), scancode: scancode.map(|s| s as i32), keymod: keymod.bits(), repeat },
+      Event::TextInput { window_id, text, .. } => RecordedEvent::TextInput { window_id, text },
+      Event::TextEditing { window_id, text, start, length, .. } => RecordedEvent::TextEditing { window_id, text, start, length },
+      Event::MouseMotion { window_id, which, mousestate, x, y, xrel, yrel, .. } =>
+        RecordedEvent::MouseMotion { window_id, which, state: mousestate.to_sdl_state(), x, y, xrel, yrel },
+      Event::MouseButtonDown { window_id, which, mouse_btn, clicks, x, y, .. } =>
+        RecordedEvent::MouseButtonDown { window_id, which, button: mouse_btn as u8, clicks, x, y },
+      Event::MouseButtonUp { window_id, which, mouse_btn, clicks, x, y, .. } =>
+        RecordedEvent::MouseButtonUp { window_id, which, button: mouse_btn as u8, clicks, x, y },
+      Event::MouseWheel { window_id, which, x, y, direction, precise_x, precise_y, mouse_x, mouse_y, .. } =>
+        RecordedEvent::MouseWheel { window_id, which, x, y, direction: direction.to_ll(), precise_x, precise_y, mouse_x, mouse_y },
+      Event::Window { window_id, win_event, .. } => match win_event {
+        WindowEvent::Resized(w, h) => RecordedEvent::WindowResized { window_id, w, h },
+        WindowEvent::FocusGained => RecordedEvent::WindowFocusGained { window_id },
+        WindowEvent::FocusLost => RecordedEvent::WindowFocusLost { window_id },
+        WindowEvent::Enter => RecordedEvent::WindowEnter { window_id },
+        WindowEvent::Leave => RecordedEvent::WindowLeave { window_id },
+        _ => return None
+      },
+      Event::ControllerButtonDown { which, button, .. } => RecordedEvent::ControllerButtonDown { which, button: button.string() },
+      Event::ControllerButtonUp { which, button, .. } => RecordedEvent::ControllerButtonUp { which, button: button.string() },
+      Event::ControllerAxisMotion { which, axis, value, .. } => RecordedEvent::ControllerAxisMotion { which, axis: axis.string(), value },
+      Event::FingerDown { touch_id, finger_id, x, y, dx, dy, pressure, .. } =>
+        RecordedEvent::FingerDown(RecordedFinger { touch_id, finger_id, x, y, dx, dy, pressure }),
+      Event::FingerMotion { touch_id, finger_id, x, y, dx, dy, pressure, .. } =>
+        RecordedEvent::FingerMotion(RecordedFinger { touch_id, finger_id, x, y, dx, dy, pressure }),
+      Event::FingerUp { touch_id, finger_id, x, y, dx, dy, pressure, .. } =>
+        RecordedEvent::FingerUp(RecordedFinger { touch_id, finger_id, x, y, dx, dy, pressure }),
+      Event::MultiGesture { touch_id, d_theta, d_dist, x, y, num_fingers, .. } =>
+        RecordedEvent::MultiGesture { touch_id, d_theta, d_dist, x, y, num_fingers },
+      _ => return None
+    };
+    Some(rec)
+  }
+
+  // None if the recorded data is not valid (e.g. an unknown gamepad button name), or not an SDL event
+  pub fn to_event(&self) -> Option<Event> {
+    let timestamp = 0;
+    let ev = match self.clone() {
+      RecordedEvent::Quit => Event::Quit { timestamp },
+      RecordedEvent::KeyDown { window_id, keycode, scancode, keymod, repeat } => Event::KeyDown { timestamp, window_id,
+        keycode: keycode.and_then(Keycode::from_i32), scancode: scancode.and_then(Scancode::from_i32), keymod: Mod::from_bits_truncate(keymod), repeat },
+      RecordedEvent::KeyUp { window_id, keycode, scancode, keymod, repeat } => Event::KeyUp { timestamp, window_id,
+        keycode: keycode.and_then(Keycode::from_i32), scancode: scancode.and_then(Scancode::from_i32), keymod: Mod::from_bits_truncate(keymod), repeat },
+      RecordedEvent::TextInput { window_id, text } => Event::TextInput { timestamp, window_id, text },
+      RecordedEvent::TextEditing { window_id, text, start, length } => Event::TextEditing { timestamp, window_id, text, start, length },
+      RecordedEvent::MouseMotion { window_id, which, state, x, y, xrel, yrel } =>
+        Event::MouseMotion { timestamp, window_id, which, mousestate: MouseState::from_sdl_state(state), x, y, xrel, yrel },
+      RecordedEvent::MouseButtonDown { window_id, which, button, clicks, x, y } =>
+        Event::MouseButtonDown { timestamp, window_id, which, mouse_btn: MouseButton::from_ll(button), clicks, x, y },
+      RecordedEvent::MouseButtonUp { window_id, which, button, clicks, x, y } =>
+        Event::MouseButtonUp { timestamp, window_id, which, mouse_btn: MouseButton::from_ll(button), clicks, x, y },
+      RecordedEvent::MouseWheel { window_id, which, x, y, direction, precise_x, precise_y, mouse_x, mouse_y } =>
+        Event::MouseWheel { timestamp, window_id, which, x, y, direction: MouseWheelDirection::from_ll(direction), precise_x, precise_y, mouse_x, mouse_y },
+      RecordedEvent::WindowResized { window_id, w, h } => Event::Window { timestamp, window_id, win_event: WindowEvent::Resized(w, h) },
+      RecordedEvent::WindowFocusGained { window_id } => Event::Window { timestamp, window_id, win_event: WindowEvent::FocusGained },
+      RecordedEvent::WindowFocusLost { window_id } => Event::Window { timestamp, window_id, win_event: WindowEvent::FocusLost },
+      RecordedEvent::WindowEnter { window_id } => Event::Window { timestamp, window_id, win_event: WindowEvent::Enter },
+      RecordedEvent::WindowLeave { window_id } => Event::Window { timestamp, window_id, win_event: WindowEvent::Leave },
+      RecordedEvent::ControllerButtonDown { which, button } => Event::ControllerButtonDown { timestamp, which, button: Button::from_string(&button)? },
+      RecordedEvent::ControllerButtonUp { which, button } => Event::ControllerButtonUp { timestamp, which, button: Button::from_string(&button)? },
+      RecordedEvent::ControllerAxisMotion { which, axis, value } => Event::ControllerAxisMotion { timestamp, which, axis: Axis::from_string(&axis)?, value },
+      RecordedEvent::GamepadConnected(_) | RecordedEvent::GamepadDisconnected { .. } => return None,
+      RecordedEvent::FingerDown(f) => Event::FingerDown { timestamp, touch_id: f.touch_id, finger_id: f.finger_id,
+        x: f.x, y: f.y, dx: f.dx, dy: f.dy, pressure: f.pressure },
+      RecordedEvent::FingerMotion(f) => Event::FingerMotion { timestamp, touch_id: f.touch_id, finger_id: f.finger_id,
+        x: f.x, y: f.y, dx: f.dx, dy: f.dy, pressure: f.pressure },
+      RecordedEvent::FingerUp(f) => Event::FingerUp { timestamp, touch_id: f.touch_id, finger_id: f.finger_id,
+        x: f.x, y: f.y, dx: f.dx, dy: f.dy, pressure: f.pressure },
+      RecordedEvent::MultiGesture { touch_id, d_theta, d_dist, x, y, num_fingers } =>
+        Event::MultiGesture { timestamp, touch_id, d_theta, d_dist, x, y, num_fingers },
+    };
+    Some(ev)
+  }
+
+  fn to_replayed(&self) -> Option<Replayed> {
+    match self {
+      RecordedEvent::GamepadConnected(pad) => Some(Replayed::GamepadConnected(pad.clone())),
+      RecordedEvent::GamepadDisconnected { id } => Some(Replayed::GamepadDisconnected(*id)),
+      _ => self.to_event().map(Replayed::Event)
+    }
+  }
+}
+
+// What a replayed frame gives back
+#[derive(Debug, PartialEq)]
+pub(crate) enum Replayed {
+  Event(Event),
+  GamepadConnected(GamepadInfo),
+  GamepadDisconnected(u32),
+}
+
+// One line of the file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedFrame {
+  pub frame: u64,
+  pub dt: f32,
+  pub events: Vec<RecordedEvent>,
+}
+
+pub(crate) fn replay_path(name: &str) -> PathBuf {
+  infraglobals::get_replays_path().join(format!("{}.jsonl", name))
+}
+
+pub(crate) struct Recorder {
+  writer: BufWriter<File>,
+  frame: u64,
+  events: Vec<RecordedEvent>,
+}
+
+impl Recorder {
+  pub(crate) fn create(path: &Path) -> Result<Self, String> {
+    if let Some(dir) = path.parent() {
+      std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    info!(target: TRAREPLAY, "Recording the inputs to {:?}", path);
+    Ok(Self { writer: BufWriter::new(file), frame: 0, events: Vec::new() })
+  }
+
+  pub(crate) fn record(&mut self, event: &Event) {
+    if let Some(rec) = RecordedEvent::from_event(event) {
+      self.events.push(rec);
+    }
+  }
+
+  // The gamepads hot-plug, once the engine opened (or closed) the pad
+  pub(crate) fn record_hotplug(&mut self, action: &Action) {
+    let rec = match action {
+      Action::GamepadConnected { pad } => RecordedEvent::GamepadConnected(pad.clone()),
+      Action::GamepadDisconnected { id } => RecordedEvent::GamepadDisconnected { id: *id },
+      _ => return
+    };
+    self.events.push(rec);
+  }
+
+  // Flushed every frame : the recording must survive a crash, that's the point
+  pub(crate) fn end_frame(&mut self, dt: f32) {
+    let frame = RecordedFrame { frame: self.frame, dt, events: std::mem::take(&mut self.events) };
+    self.frame += 1;
+    let res = serde_json::to_writer(&mut self.writer, &frame).map_err(|e| e.to_string())
+      .and_then(|_| writeln!(self.writer).map_err(|e| e.to_string()))
+      .and_then(|_| self.writer.flush().map_err(|e| e.to_string()));
+    if let Err(e) = res {
+      warn!(target: TRAREPLAY, "Cannot write the recording: {}", e);
+    }
+  }
+}
+
+pub(crate) struct Player {
+  lines: Lines<BufReader<File>>,
+}
+
+impl Player {
+  pub(crate) fn open(path: &Path) -> Result<Self, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    info!(target: TRAREPLAY, "Replaying the inputs from {:?}", path);
+    Ok(Self { lines: BufReader::new(file).lines() })
+  }
+
+  // None at the end of the file (or on a corrupted line)
+  pub(crate) fn next_frame(&mut self) -> Option<(f32, Vec<Replayed>)> {
+    let line = self.lines.next()?.ok()?;
+    let frame: RecordedFrame = serde_json::from_str(&line).map_err(|e| {
+      warn!(target: TRAREPLAY, "Corrupted replay line, stopping: {}", e);
+    }).ok()?;
+    let events = frame.events.iter().filter_map(|rec| rec.to_replayed().or_else(|| {
+      warn!(target: TRAREPLAY, "Cannot replay {:?} (frame {})", rec, frame.frame);
+      None
+    })).collect();
+    Some((frame.dt, events))
+  }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_event_round_trip() {
+    let events = vec![
+      Event::Quit { timestamp: 0 },
+      Event::MouseButtonDown { timestamp: 0, window_id: 1, which: 0, mouse_btn: MouseButton::Right, clicks: 2, x: 10, y: 20 },
+      Event::MouseMotion { timestamp: 0, window_id: 1, which: 0, mousestate: MouseState::from_sdl_state(1), x: 5, y: 6, xrel: -1, yrel: 2 },
+      Event::MouseWheel { timestamp: 0, window_id: 1, which: 0, x: 0, y: -1, direction: MouseWheelDirection::Flipped,
+        precise_x: 0.0, precise_y: -1.0, mouse_x: 30, mouse_y: 40 },
+      Event::TextInput { timestamp: 0, window_id: 1, text: "é!".to_owned() },
+      Event::Window { timestamp: 0, window_id: 1, win_event: WindowEvent::Resized(800, 600) },
+      Event::FingerUp { timestamp: 0, touch_id: 3, finger_id: 4, x: 0.5, y: 0.25, dx: 0.0, dy: 0.1, pressure: 1.0 },
+    ];
+    for ev in events {
+      let rec = RecordedEvent::from_event(&ev).unwrap();
+      // Through JSON, as in the file
+      let rec: RecordedEvent = serde_json::from_str(&serde_json::to_string(&rec).unwrap()).unwrap();
+      assert_eq!(rec.to_event().unwrap(), ev);
+    }
+    assert!(RecordedEvent::from_event(&Event::Window { timestamp: 0, window_id: 1, win_event: WindowEvent::Shown }).is_none());
+  }
+
+  #[test]
+  fn test_record_and_replay() {
+    let path = infraglobals::test_temp_dir("replay").join("test.jsonl");
+    let mut recorder = Recorder::create(&path).unwrap();
+    recorder.record(&Event::Quit { timestamp: 0 });
+    recorder.end_frame(0.016);
+    recorder.end_frame(0.02); // A frame without any event
+    recorder.record(&Event::Window { timestamp: 0, window_id: 1, win_event: WindowEvent::Leave });
+    recorder.end_frame(0.017);
+    // The hot-plug as the scenes saw it, not the SDL events
+    let pad = GamepadInfo { id: 4, name: "Pad".to_owned(), has_rumble: true };
+    recorder.record(&Event::ControllerDeviceAdded { timestamp: 0, which: 0 });
+    recorder.record_hotplug(&Action::GamepadConnected { pad: pad.clone() });
+    recorder.record_hotplug(&Action::GamepadDisconnected { id: 4 });
+    recorder.end_frame(0.016);
+    drop(recorder);
+
+    let mut player = Player::open(&path).unwrap();
+    assert_eq!(player.next_frame(), Some((0.016, vec![Replayed::Event(Event::Quit { timestamp: 0 })])));
+    assert_eq!(player.next_frame(), Some((0.02, vec![])));
+    assert_eq!(player.next_frame(), Some((0.017, vec![Replayed::Event(Event::Window { timestamp: 0, window_id: 1, win_event: WindowEvent::Leave })])));
+    assert_eq!(player.next_frame(), Some((0.016, vec![Replayed::GamepadConnected(pad), Replayed::GamepadDisconnected(4)])));
+    assert_eq!(player.next_frame(), None);
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+  }
+}
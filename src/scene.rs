@@ -1,8 +1,9 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, ops::Range};
+use bitflags::bitflags;
 use sdl2::{event::Event, rect::Rect};
 use taffy::NodeId;
 use tracing::{debug, warn};
-use crate::{action::{Action, EventKind}, action_bus::{ActionBus, ActionPriv}, Renderer, layout_manager::LayoutManager, utils::is_point_in_rect};
+use crate::{action::{Action, DragPayload, EventKind}, action_bus::{ActionBus, ActionPriv}, Renderer, layout_manager::LayoutManager, hit_shape::HitShape};
 
 // Unique identifier for each scene.
 pub type SceneID = u64;
@@ -15,6 +16,17 @@ pub type SceneID = u64;
 
 const TRASCENE: &str = "hg::scene";
 
+bitflags! {
+  /// Runtime state of a scene, set with Action::SetSceneFlags.
+  /// The flags apply to the whole subtree : pausing a scene pauses its children too.
+  #[derive(Clone, Copy, Debug, PartialEq)]
+  pub struct SceneFlags: u8 {
+    const Hidden        = 1 << 0; // render() is not called
+    const Paused        = 1 << 1; // update() is not called
+    const InputDisabled = 1 << 2; // SDL events are not propagated to the scene
+  }
+}
+
 pub trait Scene {
   fn init(&mut self, _action_bus: &mut ActionBus) {}
 
@@ -27,12 +39,46 @@ pub trait Scene {
   fn is_modal(&self) -> bool { false }
   fn handle_action(&mut self, _action: &Action, _origin: Option<SceneID>, _action_bus: &mut ActionBus) -> bool { false }
   fn left_click_zone(&self) -> Option<Rect> { None }
+  // Clickable shape : by default the left click zone. Override for round buttons, map regions...
+  fn hit_shape(&self) -> Option<HitShape> { self.left_click_zone().map(HitShape::Rect) }
+  // Whether (x, y) is on the scene, for the clicks, the hovering, the drops and the touch.
+  fn hit_test(&self, x: i32, y: i32) -> bool { self.hit_shape().is_some_and(|shape| shape.contains(x, y)) }
 
   // TODO. a rectangle position is not enough. The scene may want to know its "border" size for example
   fn pos_changed(&mut self, _pos: Rect) {  }
   fn subscriptions(&self) -> EventKind { EventKind::NotAnEvent }
   fn name(&self) -> &str { "Unknown" }
   fn is_immediate(&self) -> bool { false } // private 
+  // Drop target : the scene will get DragEnter/DragOver/DragLeave/Drop for that payload
+  fn accepts_drop(&self, _payload: &DragPayload) -> bool { false }
+  // Keyboard focus : Tab / Shift+Tab, the D-pad or a click can give it the focus (FocusIn / FocusOut).
+  // The focused scene gets the keyboard, text and gamepad button events first.
+  fn is_focusable(&self) -> bool { false }
+
+  // Routed actions (clicks, wheel, touch, and the keyboard of the focused scene) : called on the 
+  // target and on its ancestors, see Phase. Return true to stop the propagation.
+  // By default the target handles it as usual, the ancestors ignore it.
+  fn handle_routed(&mut self, action: &Action, phase: Phase, _target: SceneID, action_bus: &mut ActionBus) -> bool {
+    phase == Phase::Target && self.handle_action(action, None, action_bus)
+  }
+
+  // Lifecycle hooks
+  // on_enter : the scene has been added to the stack (called right after init)
+  fn on_enter(&mut self, _action_bus: &mut ActionBus) {}
+  // on_exit : the scene is about to be removed (its children get it first)
+  fn on_exit(&mut self, _action_bus: &mut ActionBus) {}
+  // on_focus : the scene can receive input, i.e. there is no modal scene above it (again)
+  fn on_focus(&mut self, _action_bus: &mut ActionBus) {}
+  // on_blur : a modal scene appeared above it, or its input got disabled
+  fn on_blur(&mut self, _action_bus: &mut ActionBus) {}
+}
+
+// Phases of a routed action, like in the DOM
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+  Capture, // On the ancestors of the target, from the root down
+  Target,
+  Bubble, // On the ancestors of the target, back up to the root
 }
 
 pub(crate) struct ScenePriv {
@@ -41,11 +87,15 @@ pub(crate) struct ScenePriv {
   pub(crate) parent: SceneID,
   children: Vec<SceneID>,
   pub(crate) scene: Box<dyn Scene>,
+  flags: SceneFlags, // Flags set on that very scene
+  eff_flags: SceneFlags, // Flags set on the scene or inherited from its ancestors
+  interactive: bool, // Last state notified with on_focus / on_blur
 }
 
 impl ScenePriv {
   pub fn new(id: SceneID, parent: SceneID, scene: Box<dyn Scene>) -> Self {
-    Self { id, taffy_id: None, parent, children: vec![], scene }  
+    Self { id, taffy_id: None, parent, children: vec![], scene, 
+      flags: SceneFlags::empty(), eff_flags: SceneFlags::empty(), interactive: false }  
   }
 
   pub fn get_id(&self) -> SceneID {
@@ -95,13 +145,15 @@ impl SceneStack
   {
     let new_scene_id = self.next_scene_id;
     debug!("New scene requested : id=<{}>, name=<{}>, parent=<{}>", new_scene_id, scene.name(), parent);
-    self.scenes_priv[layer].push(ScenePriv::new(new_scene_id, parent, scene));
+    let mut scene_priv = ScenePriv::new(new_scene_id, parent, scene);
 
     // Add children 
      // If detached mode, pass 0 anyway, so, .. ok 
     if let Some(parent_scene) = self.get_scene(parent) {
       parent_scene.children.push(new_scene_id); // If parent scene is dead we are bad 
+      scene_priv.eff_flags = parent_scene.eff_flags; // A child of a hidden scene is hidden, etc.
     }
+    self.scenes_priv[layer].push(scene_priv);
     self.next_scene_id += 1;
   }
 
@@ -133,6 +185,43 @@ impl SceneStack
     found
   }
 
+  // Remove a scene and its descendants, calling their on_exit hook before (children first).
+  // Returns the IDs of the removed scenes.
+  pub(crate) fn close_scene(&mut self, id: SceneID, action_bus: &mut ActionBus) -> HashSet<SceneID> {
+    let mut ids_to_remove = HashSet::new();
+    if id == 0 || self.get_scene(id).is_none() {
+      return ids_to_remove;
+    }
+    self.exit_subtree(id, action_bus);
+    self.collect_descendants(id, &mut ids_to_remove);
+    self.remove_scene(id);
+    ids_to_remove
+  }
+
+  fn exit_subtree(&mut self, id: SceneID, action_bus: &mut ActionBus) {
+    let next_scene_id = self.next_scene_id;
+    let Some(sc_p) = self.get_scene(id) else { return; };
+    for child_id in sc_p.children.clone() {
+      self.exit_subtree(child_id, action_bus);
+    }
+    if let Some(sc_p) = self.get_scene(id) {
+      debug!(target: TRASCENE, "on_exit id=<{}>", id);
+      action_bus.prepare(id, next_scene_id);
+      sc_p.scene.on_exit(action_bus);
+    }
+  }
+
+  // IDs of a scene and all its descendants
+  pub(crate) fn subtree(&self, id: SceneID) -> HashSet<SceneID> {
+    let mut ids = HashSet::new();
+    self.collect_descendants(id, &mut ids);
+    ids
+  }
+
+  pub(crate) fn layer_of(&self, id: SceneID) -> Option<usize> {
+    self.scenes_priv.iter().position(|layer_vec| layer_vec.iter().any(|sc_p| sc_p.id == id))
+  }
+
   // Recursive function to collect all descendants using the `children` vector
   fn collect_descendants(&self, scene_id: SceneID, descendants: &mut HashSet<SceneID>) 
   {
@@ -151,14 +240,88 @@ impl SceneStack
     }
   }
 
+  // Set (enabled = true) or clear some flags on a scene, and refresh its subtree.
+  pub(crate) fn set_flags(&mut self, id: SceneID, flags: SceneFlags, enabled: bool) -> bool {
+    let Some(sc_p) = self.get_scene(id) else {
+      warn!(target: TRASCENE, "set_flags: no scene found for id {}", id);
+      return false;
+    };
+    sc_p.flags.set(flags, enabled);
+    let parent = sc_p.parent;
+
+    let inherited = self.get_scene(parent).map_or(SceneFlags::empty(), |p| p.eff_flags);
+    self.propagate_flags(id, inherited);
+    true
+  }
+
+  // The flags set on the scene itself (not the inherited ones)
+  pub(crate) fn own_flags(&mut self, id: SceneID) -> Option<SceneFlags> {
+    self.get_scene(id).map(|sc_p| sc_p.flags)
+  }
+
+  // Effective flags of a scene (its own ones + the ones inherited from its ancestors)
+  pub fn flags(&mut self, id: SceneID) -> Option<SceneFlags> {
+    self.get_scene(id).map(|sc_p| sc_p.eff_flags)
+  }
+
+  fn propagate_flags(&mut self, id: SceneID, inherited: SceneFlags) {
+    let (eff_flags, children) = match self.get_scene(id) {
+      Some(sc_p) => {
+        sc_p.eff_flags = sc_p.flags | inherited;
+        (sc_p.eff_flags, sc_p.children.clone())
+      },
+      None => return
+    };
+    for child_id in children {
+      self.propagate_flags(child_id, eff_flags);
+    }
+  }
+
   pub fn init(&mut self, id: SceneID, action_bus: &mut ActionBus) {
     self.get_scene(id).unwrap().scene.init(action_bus);
   }
 
+  // (The bus must already be prepared for that scene, as for init)
+  pub(crate) fn enter(&mut self, id: SceneID, action_bus: &mut ActionBus) {
+    self.get_scene(id).unwrap().scene.on_enter(action_bus);
+  }
+
+  // Call on_focus / on_blur on the scenes whose "interactive" state changed.
+  // Just like the input propagation : scenes are interactive from the top of the stack
+  // down to the first modal scene (included). Input-disabled scenes are not interactive.
+  pub(crate) fn refresh_focus(&mut self, action_bus: &mut ActionBus) {
+    let next_scene_id = self.next_scene_id;
+    let mut reachable = true;
+    for layer in self.scenes_priv.iter_mut().rev() {
+      for sc_p in layer.iter_mut().rev() {
+        let interactive = reachable && !sc_p.eff_flags.contains(SceneFlags::InputDisabled);
+        if interactive && sc_p.scene.is_modal() {
+          reachable = false; // Everything below is blurred
+        }
+        if interactive == sc_p.interactive {
+          continue;
+        }
+        sc_p.interactive = interactive;
+        action_bus.prepare(sc_p.id, next_scene_id);
+        if interactive {
+          debug!(target: TRASCENE, "on_focus id=<{}>", sc_p.id);
+          sc_p.scene.on_focus(action_bus);
+        }
+        else {
+          debug!(target: TRASCENE, "on_blur id=<{}>", sc_p.id);
+          sc_p.scene.on_blur(action_bus);
+        }
+      }
+    }
+  }
+
   pub fn update_all(&mut self, delta_time: f32, action_bus: &mut ActionBus) {
     // Update bottom to top, just like rendering
     for layer in &mut self.scenes_priv {
       for sc_p in layer.iter_mut() {
+        if sc_p.eff_flags.contains(SceneFlags::Paused) {
+          continue;
+        }
         action_bus.prepare(sc_p.id, self.next_scene_id);
         sc_p.scene.update(delta_time, action_bus);
       }
@@ -167,8 +330,20 @@ impl SceneStack
 
   // Paint the scenes from the lowest to the highest in the stack
   pub fn render_all(&mut self, renderer: &mut Renderer, action_bus: &mut ActionBus) {
-    for layer in &mut self.scenes_priv {
+    self.render_some(renderer, action_bus, 0..MAX_LAY, |_| true);
+  }
+
+  // Same as render_all, restricted to some layers and to the scenes accepted by the filter
+  pub(crate) fn render_some(&mut self, renderer: &mut Renderer, action_bus: &mut ActionBus, 
+    layers: Range<usize>, filter: impl Fn(SceneID) -> bool) 
+  {
+    let end = layers.end.min(self.scenes_priv.len());
+    let start = layers.start.min(end);
+    for layer in &mut self.scenes_priv[start..end] {
       for scene_priv in layer.iter_mut() {
+        if scene_priv.eff_flags.contains(SceneFlags::Hidden) || !filter(scene_priv.id) {
+          continue;
+        }
         scene_priv.scene.render(renderer);
 
         if scene_priv.scene.is_immediate() {
@@ -181,8 +356,41 @@ impl SceneStack
 
   // We are already doing a match {} on sdl events in the main loop. So we directly give the event kind here
   // Otherwise if we call action.event_kind we're back traversing every existing action.
-  pub fn propagate_sdl2_to_subscribers(&mut self, action_bus: &mut ActionBus, action: Action, event_kind: EventKind)
+  // Returns the scene which handled the event, if any
+  pub fn propagate_sdl2_to_subscribers(&mut self, action_bus: &mut ActionBus, action: Action, event_kind: EventKind) -> Option<SceneID>
   {
+    self.propagate_sdl2_except(action_bus, action, event_kind, &[])
+  }
+
+  // Same, skipping one scene (which already got the event, e.g. the focused one)
+  pub(crate) fn propagate_sdl2_except(&mut self, action_bus: &mut ActionBus, action: Action, event_kind: EventKind, 
+    except: &[SceneID]) -> Option<SceneID>
+  {
+    // Clicks and wheel events only go to the scenes under the pointer
+    let hit_point = match action {
+      Action::SdlEvent(Event::MouseButtonDown { x, y, .. }) => Some((x, y)),
+      Action::SdlEvent(Event::MouseWheel { mouse_x, mouse_y, .. }) => Some((mouse_x, mouse_y)),
+      _ => None
+    };
+    self.propagate_at(action_bus, action, event_kind, hit_point, except)
+  }
+
+  // Same, the hit point being given : only the scenes whose click zone contains it get the action.
+  // E.g. touch events, whose coordinates are normalized.
+  // With a hit point, the action is first routed through the scene tree (see route) : the scene 
+  // under the point is the target. If no scene on that path stops it, the action goes down the 
+  // layers as usual (handle_action), the target excepted.
+  pub(crate) fn propagate_at(&mut self, action_bus: &mut ActionBus, action: Action, event_kind: EventKind, 
+    hit_point: Option<(i32, i32)>, except: &[SceneID]) -> Option<SceneID>
+  {
+    let mut except = except.to_vec();
+    if let Some(target) = hit_point.and_then(|(x, y)| self.hit_target(x, y)) {
+      if let Some(id) = self.route(action_bus, &action, &event_kind, target, false) {
+        return Some(id);
+      }
+      except.extend(self.route_path(target)); // The target and its ancestors already got it
+    }
+
     // if nobody subscribed to that event, just return (TODO)
     // Starting from the top layer to the bottom (reverse order)
     for layer_index in (0..self.scenes_priv.len()).rev() 
@@ -192,6 +400,10 @@ impl SceneStack
       {
         let scene_priv = &mut layer[sc_idx];
 
+        // Disabled scenes are just transparent to the events (even modal ones)
+        if scene_priv.eff_flags.contains(SceneFlags::InputDisabled) {
+          continue;
+        }
         // No need to do anything if the scene didn't subscribe to that action.
         if !scene_priv.scene.subscriptions().intersects(event_kind.clone()) {
           continue;
@@ -199,30 +411,177 @@ impl SceneStack
         // Filter unwanted clicks if out of the clickable zone.
         // It also filters if there is NO clickable zone, but it is rarer 
         // Since a scene with no clickable zone shouldn't subscribe to click events!...
-        if let Action::SdlEvent (Event::MouseButtonDown{x, y, ..}) = action {
-          let clickable = scene_priv.scene.left_click_zone()
-          .is_some_and(|rect| is_point_in_rect(&rect, x, y));
+        if let Some((x, y)) = hit_point {
+          let clickable = scene_priv.scene.hit_test(x, y);
 
           if !clickable && scene_priv.scene.is_modal() { // x, y not in clickable zone
-            return; // If x, y not in current scene and scene is modal, just return.
+            return None; // If x, y not in current scene and scene is modal, just return.
           }
           else if !clickable { // Just fall through the next scenes.
             continue;
           }
         }
 
-        // Call the user handler
-        action_bus.prepare(scene_priv.id, self.next_scene_id);
-        if scene_priv.scene.handle_action(&action, None, action_bus) {
-          return;
-        } // If the event was consumed or the scene is modal, I stop traversing
+        // Call the user handler (unless that scene already got the event)
+        if !except.contains(&scene_priv.id) {
+          action_bus.prepare(scene_priv.id, self.next_scene_id);
+          if scene_priv.scene.handle_action(&action, None, action_bus) {
+            return Some(scene_priv.id);
+          } // If the event was consumed or the scene is modal, I stop traversing
+        }
         
         if scene_priv.scene.is_modal() {
          // Stop looping on first modal scene even if it did not handle the event
-          return;
+          return None;
+        }
+      }
+    }
+    None
+  }
+
+  // DOM-style dispatch of an action aimed at one scene : capture phase from the root down to the
+  // parent of the target, the target itself, then bubble phase from its parent back up to the root.
+  // Only the scenes subscribed to that kind of event are called (the target always is if force_target). 
+  // Any of them can stop the propagation by returning true : returns that scene.
+  pub(crate) fn route(&mut self, action_bus: &mut ActionBus, action: &Action, event_kind: &EventKind, 
+    target: SceneID, force_target: bool) -> Option<SceneID> 
+  {
+    let ancestors = self.route_path(target).split_off(1); // Parent first
+    let path: Vec<(SceneID, Phase)> = ancestors.iter().rev().map(|id| (*id, Phase::Capture))
+      .chain(std::iter::once((target, Phase::Target)))
+      .chain(ancestors.iter().map(|id| (*id, Phase::Bubble)))
+      .collect();
+
+    let next_scene_id = self.next_scene_id;
+    for (id, phase) in path {
+      let Some(sc_p) = self.get_scene(id) else { continue; };
+      if sc_p.eff_flags.contains(SceneFlags::InputDisabled) {
+        continue;
+      }
+      let forced = force_target && phase == Phase::Target;
+      if !forced && !sc_p.scene.subscriptions().intersects(event_kind.clone()) {
+        continue;
+      }
+      action_bus.prepare(id, next_scene_id);
+      if sc_p.scene.handle_routed(action, phase, target, action_bus) {
+        debug!(target: TRASCENE, "Routed action to {} stopped by {} ({:?})", target, id, phase);
+        return Some(id);
+      }
+    }
+    None
+  }
+
+  // The scene and its ancestors, up to the engine root (included)
+  fn route_path(&self, id: SceneID) -> Vec<SceneID> {
+    let mut path = Vec::new();
+    let mut current = Some(id);
+    while let Some(cur) = current {
+      let Some(sc_p) = self.scenes_priv.iter().flatten().find(|sc_p| sc_p.id == cur) else { break; };
+      path.push(cur);
+      current = (cur != 0).then_some(sc_p.parent);
+    }
+    path
+  }
+
+  // The topmost scene under (x, y), whatever its subscriptions. Nothing below a modal scene.
+  pub(crate) fn hit_target(&self, x: i32, y: i32) -> Option<SceneID> {
+    for layer in self.scenes_priv.iter().rev() {
+      for sc_p in layer.iter().rev() {
+        if sc_p.eff_flags.contains(SceneFlags::InputDisabled) {
+          continue;
+        }
+        if sc_p.scene.hit_test(x, y) {
+          return Some(sc_p.id);
+        }
+        if sc_p.scene.is_modal() {
+          return None;
+        }
+      }
+    }
+    None
+  }
+
+  // The scene under (x, y) among the ones subscribed to that kind of event. 
+  // Same rules as the click propagation : from the top layer to the bottom, and nothing
+  // below a modal scene.
+  pub(crate) fn scene_at(&self, x: i32, y: i32, event_kind: &EventKind) -> Option<SceneID> {
+    for layer in self.scenes_priv.iter().rev() {
+      for sc_p in layer.iter().rev() {
+        if sc_p.eff_flags.contains(SceneFlags::InputDisabled) 
+          || !sc_p.scene.subscriptions().intersects(event_kind.clone()) {
+          continue;
+        }
+        if sc_p.scene.hit_test(x, y) {
+          return Some(sc_p.id);
+        }
+        if sc_p.scene.is_modal() {
+          return None;
+        }
+      }
+    }
+    None
+  }
+
+  // The drop target under (x, y) : the topmost scene accepting the payload. 
+  // Nothing below a modal scene, and a scene can't be dropped on itself.
+  pub(crate) fn drop_target_at(&self, x: i32, y: i32, payload: &DragPayload, source: SceneID) -> Option<SceneID> {
+    for layer in self.scenes_priv.iter().rev() {
+      for sc_p in layer.iter().rev() {
+        if sc_p.eff_flags.contains(SceneFlags::InputDisabled) || sc_p.id == source {
+          continue;
+        }
+        let inside = sc_p.scene.hit_test(x, y);
+        if inside && sc_p.scene.accepts_drop(payload) {
+          return Some(sc_p.id);
+        }
+        if sc_p.scene.is_modal() {
+          return None;
         }
       }
     }
+    None
+  }
+
+  // Whether a scene can get the keyboard focus right now : focusable, visible, and reachable by 
+  // the input (see refresh_focus)
+  pub(crate) fn can_focus(&self, id: SceneID) -> bool {
+    self.scenes_priv.iter().flatten().any(|sc_p| sc_p.id == id && Self::focus_candidate(sc_p))
+  }
+
+  fn focus_candidate(sc_p: &ScenePriv) -> bool {
+    sc_p.interactive && !sc_p.eff_flags.contains(SceneFlags::Hidden) && sc_p.scene.is_focusable()
+  }
+
+  // The scenes which can get the focus, in the Tab order : the order of their layout nodes 
+  // (rank given by the layout manager), then the ones without layout in the stack order.
+  pub(crate) fn focus_order(&self, node_rank: impl Fn(NodeId) -> Option<usize>) -> Vec<SceneID> {
+    let mut candidates: Vec<(usize, usize, SceneID)> = self.scenes_priv.iter().flatten()
+      .enumerate()
+      .filter(|(_, sc_p)| Self::focus_candidate(sc_p))
+      .map(|(pos, sc_p)| (sc_p.taffy_id.and_then(&node_rank).unwrap_or(usize::MAX), pos, sc_p.id))
+      .collect();
+    candidates.sort();
+    candidates.into_iter().map(|(_, _, id)| id).collect()
+  }
+
+  pub(crate) fn zone(&self, id: SceneID) -> Option<Rect> {
+    self.scenes_priv.iter().flatten().find(|sc_p| sc_p.id == id).and_then(|sc_p| sc_p.scene.hit_shape()?.bounds())
+  }
+
+  pub(crate) fn contains(&self, id: SceneID) -> bool {
+    self.layer_of(id).is_some()
+  }
+
+  // Give an action to one scene only. Returns true if it handled it.
+  pub(crate) fn send_to(&mut self, id: SceneID, action: &Action, origin: Option<SceneID>, action_bus: &mut ActionBus) -> bool {
+    let next_scene_id = self.next_scene_id;
+    match self.get_scene(id) {
+      Some(sc_p) => {
+        action_bus.prepare(id, next_scene_id);
+        sc_p.scene.handle_action(action, origin, action_bus)
+      },
+      None => false
+    }
   }
 
   pub(crate) fn propagate_ham_to_subscribers(&mut self, action_bus: &mut ActionBus, action_p: ActionPriv) 
@@ -251,6 +610,34 @@ impl SceneStack
     }
   }
 
+  // Give an engine action to the scenes, from the top to the bottom (whatever the subscriptions
+  // and modality), until one of them handles it. Returns true if a scene handled it (the quit veto).
+  pub(crate) fn broadcast(&mut self, action_bus: &mut ActionBus, action: &Action) -> bool {
+    let next_scene_id = self.next_scene_id;
+    for layer in self.scenes_priv.iter_mut().rev() {
+      for sc_p in layer.iter_mut().rev() {
+        action_bus.prepare(sc_p.id, next_scene_id);
+        if sc_p.scene.handle_action(action, None, action_bus) {
+          debug!(target: TRASCENE, "Broadcast action handled by {}", sc_p.id);
+          return true;
+        }
+      }
+    }
+    false
+  }
+
+  // Give an engine action to every scene, whatever the subscriptions, the modality and what the 
+  // scenes return (e.g. the gamepads hot-plug)
+  pub(crate) fn deliver_to_all(&mut self, action_bus: &mut ActionBus, action: &Action) {
+    let next_scene_id = self.next_scene_id;
+    for layer in self.scenes_priv.iter_mut().rev() {
+      for sc_p in layer.iter_mut().rev() {
+        action_bus.prepare(sc_p.id, next_scene_id);
+        sc_p.scene.handle_action(action, None, action_bus);
+      }
+    }
+  }
+
   pub(crate) fn get_scene(&mut self, id: SceneID) -> Option<&mut ScenePriv> {
     for layer_vec in &mut self.scenes_priv {
       if let Some(pos) = layer_vec.iter().position(|sc_p| sc_p.id == id) {
@@ -314,11 +701,111 @@ impl SceneStack
 #[cfg(test)]
 mod tests {
   use super::*;
+  use std::{cell::{Cell, RefCell}, rc::Rc};
 
   struct TestScene {}
   impl TestScene{fn new() -> Self { Self{} }}
   impl Scene for TestScene {}
 
+  // Records the lifecycle hooks calls in a shared journal
+  struct HookScene {
+    name: &'static str,
+    modal: bool,
+    journal: Rc<RefCell<Vec<String>>>
+  }
+  impl HookScene {
+    fn new(name: &'static str, modal: bool, journal: &Rc<RefCell<Vec<String>>>) -> Self { 
+      Self { name, modal, journal: Rc::clone(journal) } 
+    }
+    fn log(&self, hook: &str) { self.journal.borrow_mut().push(format!("{} {}", hook, self.name)); }
+  }
+  impl Scene for HookScene {
+    fn is_modal(&self) -> bool { self.modal }
+    fn on_exit(&mut self, _action_bus: &mut ActionBus) { self.log("exit"); }
+    fn on_focus(&mut self, _action_bus: &mut ActionBus) { self.log("focus"); }
+    fn on_blur(&mut self, _action_bus: &mut ActionBus) { self.log("blur"); }
+  }
+
+  // A clickable rectangle
+  struct ZoneScene {
+    zone: Rect,
+    modal: bool,
+    subs: EventKind
+  }
+  impl ZoneScene {
+    fn new(x: i32, y: i32, w: u32, h: u32, modal: bool, subs: EventKind) -> Self { 
+      Self { zone: Rect::new(x, y, w, h), modal, subs } 
+    }
+  }
+  impl Scene for ZoneScene {
+    fn is_modal(&self) -> bool { self.modal }
+    fn left_click_zone(&self) -> Option<Rect> { Some(self.zone) }
+    fn subscriptions(&self) -> EventKind { self.subs.clone() }
+  }
+
+  struct SlotScene {
+    zone: Rect,
+    accepts: &'static str,
+  }
+  impl Scene for SlotScene {
+    fn left_click_zone(&self) -> Option<Rect> { Some(self.zone) }
+    fn accepts_drop(&self, payload: &DragPayload) -> bool { payload.kind == self.accepts }
+  }
+
+  struct FocusableScene { modal: bool }
+  impl Scene for FocusableScene {
+    fn is_modal(&self) -> bool { self.modal }
+    fn is_focusable(&self) -> bool { true }
+  }
+
+  struct RoundScene {}
+  impl Scene for RoundScene {
+    fn hit_shape(&self) -> Option<HitShape> { Some(HitShape::Circle { x: 50, y: 50, radius: 10 }) }
+    fn subscriptions(&self) -> EventKind { EventKind::Pointer }
+  }
+
+  // Records the routed actions it gets, stops the propagation in one phase
+  struct RoutedScene {
+    name: &'static str,
+    zone: Option<Rect>,
+    subs: EventKind,
+    stop_in: Option<Phase>,
+    journal: Rc<RefCell<Vec<String>>>
+  }
+  impl Scene for RoutedScene {
+    fn left_click_zone(&self) -> Option<Rect> { self.zone }
+    fn subscriptions(&self) -> EventKind { self.subs.clone() }
+    fn handle_routed(&mut self, _action: &Action, phase: Phase, target: SceneID, _action_bus: &mut ActionBus) -> bool {
+      self.journal.borrow_mut().push(format!("{} {:?} {}", self.name, phase, target));
+      self.stop_in == Some(phase)
+    }
+    fn handle_action(&mut self, _action: &Action, _origin: Option<SceneID>, _action_bus: &mut ActionBus) -> bool {
+      self.journal.borrow_mut().push(format!("{} walk", self.name));
+      false
+    }
+  }
+
+  struct VetoScene {}
+  impl Scene for VetoScene {
+    fn handle_action(&mut self, action: &Action, _origin: Option<SceneID>, _action_bus: &mut ActionBus) -> bool {
+      matches!(action, Action::QuitRequested)
+    }
+  }
+
+  // Counts the actions it gets, and says it handled them
+  struct GreedyScene { heard: Rc<Cell<usize>> }
+  impl Scene for GreedyScene {
+    fn is_modal(&self) -> bool { true }
+    fn handle_action(&mut self, _action: &Action, _origin: Option<SceneID>, _action_bus: &mut ActionBus) -> bool {
+      self.heard.set(self.heard.get() + 1);
+      true
+    }
+  }
+
+  fn test_bus() -> ActionBus {
+    ActionBus::new(Rc::new(Cell::new(0)), Default::default())
+  }
+
   #[test]
   fn test_push_and_get_scene() {
     let mut stack = SceneStack::new(Box::new(TestScene::new()), NodeId::new(1));
@@ -426,4 +913,217 @@ mod tests {
       assert!(stack.get_scene(i).is_none());
     }
   }
+
+  #[test]
+  fn test_flags_subtree() {
+    let mut stack = SceneStack::new(Box::new(TestScene::new()), NodeId::new(1));
+    stack.push(3, Box::new(TestScene::new()), 1); // id 2
+    stack.push(4, Box::new(TestScene::new()), 2); // id 3
+    stack.push(2, Box::new(TestScene::new()), 1); // id 4
+
+    assert!(stack.set_flags(2, SceneFlags::Paused | SceneFlags::InputDisabled, true));
+    assert_eq!(stack.flags(2), Some(SceneFlags::Paused | SceneFlags::InputDisabled));
+    assert_eq!(stack.flags(3), Some(SceneFlags::Paused | SceneFlags::InputDisabled));
+    assert_eq!(stack.flags(4), Some(SceneFlags::empty())); // Sibling is left untouched
+    assert_eq!(stack.flags(1), Some(SceneFlags::empty()));
+
+    // A new child of a paused scene is born paused
+    stack.push(5, Box::new(TestScene::new()), 3); // id 5
+    assert_eq!(stack.flags(5), Some(SceneFlags::Paused | SceneFlags::InputDisabled));
+
+    // Own flags are kept when the ancestor ones are cleared
+    assert!(stack.set_flags(3, SceneFlags::Hidden, true));
+    assert!(stack.set_flags(2, SceneFlags::Paused | SceneFlags::InputDisabled, false));
+    assert_eq!(stack.flags(2), Some(SceneFlags::empty()));
+    assert_eq!(stack.flags(3), Some(SceneFlags::Hidden));
+    assert_eq!(stack.flags(5), Some(SceneFlags::Hidden));
+
+    assert!(!stack.set_flags(42, SceneFlags::Hidden, true));
+  }
+
+  #[test]
+  fn test_lifecycle_hooks() {
+    let journal = Rc::new(RefCell::new(Vec::new()));
+    let mut bus = test_bus();
+    let mut stack = SceneStack::new(Box::new(HookScene::new("root", false, &journal)), NodeId::new(1));
+    stack.push(2, Box::new(HookScene::new("game", false, &journal)), 1); // id 2
+    stack.push(3, Box::new(HookScene::new("hud", false, &journal)), 2); // id 3
+    stack.refresh_focus(&mut bus);
+    assert_eq!(*journal.borrow(), vec!["focus hud", "focus game", "focus root"]);
+    journal.borrow_mut().clear();
+
+    // A modal scene on top blurs everything below
+    stack.push(5, Box::new(HookScene::new("pause", true, &journal)), 1); // id 4
+    stack.refresh_focus(&mut bus);
+    assert_eq!(*journal.borrow(), vec!["focus pause", "blur hud", "blur game", "blur root"]);
+    journal.borrow_mut().clear();
+
+    // Nothing changed, nothing is called
+    stack.refresh_focus(&mut bus);
+    assert!(journal.borrow().is_empty());
+
+    // Closing the modal scene gives the focus back
+    let removed = stack.close_scene(4, &mut bus);
+    assert_eq!(removed, HashSet::from([4]));
+    stack.refresh_focus(&mut bus);
+    assert_eq!(*journal.borrow(), vec!["exit pause", "focus hud", "focus game", "focus root"]);
+    journal.borrow_mut().clear();
+
+    // Disabling the input blurs the whole subtree
+    stack.set_flags(2, SceneFlags::InputDisabled, true);
+    stack.refresh_focus(&mut bus);
+    assert_eq!(*journal.borrow(), vec!["blur hud", "blur game"]);
+    journal.borrow_mut().clear();
+
+    // Children exit first
+    let removed = stack.close_scene(2, &mut bus);
+    assert_eq!(removed, HashSet::from([2, 3]));
+    assert_eq!(*journal.borrow(), vec!["exit hud", "exit game"]);
+  }
+
+  #[test]
+  fn test_broadcast_veto() {
+    let mut bus = test_bus();
+    let mut stack = SceneStack::new(Box::new(TestScene::new()), NodeId::new(1));
+    stack.push(2, Box::new(TestScene::new()), 1); // id 2
+    assert!(!stack.broadcast(&mut bus, &Action::QuitRequested));
+
+    // Even an input-disabled scene on a low layer can veto
+    stack.push(0, Box::new(VetoScene {}), 1); // id 3
+    stack.set_flags(3, SceneFlags::InputDisabled, true);
+    assert!(stack.broadcast(&mut bus, &Action::QuitRequested));
+    assert!(!stack.broadcast(&mut bus, &Action::Quit));
+  }
+
+  #[test]
+  fn test_deliver_to_all() {
+    let mut bus = test_bus();
+    let (top, bottom) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+    let mut stack = SceneStack::new(Box::new(TestScene::new()), NodeId::new(1));
+    stack.push(1, Box::new(GreedyScene { heard: Rc::clone(&bottom) }), 1); // id 2
+    stack.push(2, Box::new(GreedyScene { heard: Rc::clone(&top) }), 1); // id 3
+
+    stack.deliver_to_all(&mut bus, &Action::GamepadDisconnected { id: 0 });
+    assert_eq!((top.get(), bottom.get()), (1, 1)); // The top one handled it, the bottom one still got it
+    assert!(stack.broadcast(&mut bus, &Action::QuitRequested));
+    assert_eq!((top.get(), bottom.get()), (2, 1));
+  }
+
+  #[test]
+  fn test_scene_at() {
+    let mut stack = SceneStack::new(Box::new(TestScene::new()), NodeId::new(1));
+    stack.push(1, Box::new(ZoneScene::new(0, 0, 100, 100, false, EventKind::Pointer)), 1); // id 2
+    stack.push(2, Box::new(ZoneScene::new(50, 50, 10, 10, false, EventKind::Pointer)), 1); // id 3
+    stack.push(3, Box::new(ZoneScene::new(0, 0, 100, 100, false, EventKind::SdlMouseClick)), 1); // id 4
+
+    assert_eq!(stack.scene_at(55, 55, &EventKind::Pointer), Some(3)); // Topmost subscribed
+    assert_eq!(stack.scene_at(10, 10, &EventKind::Pointer), Some(2));
+    assert_eq!(stack.scene_at(150, 10, &EventKind::Pointer), None);
+    assert_eq!(stack.scene_at(55, 55, &EventKind::SdlMouseClick), Some(4));
+
+    stack.set_flags(3, SceneFlags::InputDisabled, true);
+    assert_eq!(stack.scene_at(55, 55, &EventKind::Pointer), Some(2));
+
+    // Nothing below a modal scene
+    stack.push(4, Box::new(ZoneScene::new(200, 200, 10, 10, true, EventKind::Pointer)), 1); // id 5
+    assert_eq!(stack.scene_at(10, 10, &EventKind::Pointer), None);
+    assert_eq!(stack.scene_at(205, 205, &EventKind::Pointer), Some(5));
+  }
+
+  #[test]
+  fn test_drop_target_at() {
+    let mut stack = SceneStack::new(Box::new(TestScene::new()), NodeId::new(1));
+    stack.push(1, Box::new(SlotScene { zone: Rect::new(0, 0, 100, 100), accepts: "card" }), 1); // id 2
+    stack.push(2, Box::new(SlotScene { zone: Rect::new(50, 50, 10, 10), accepts: "item" }), 1); // id 3
+    let card = DragPayload::new("card", 7u32);
+    let item = DragPayload::new("item", ());
+    assert_eq!(card.data::<u32>(), Some(&7));
+
+    assert_eq!(stack.drop_target_at(55, 55, &card, 9), Some(2)); // 3 doesn't take cards
+    assert_eq!(stack.drop_target_at(55, 55, &item, 9), Some(3));
+    assert_eq!(stack.drop_target_at(10, 10, &item, 9), None);
+    assert_eq!(stack.drop_target_at(55, 55, &card, 2), None); // Not on itself
+
+    stack.set_flags(3, SceneFlags::InputDisabled, true);
+    assert_eq!(stack.drop_target_at(55, 55, &item, 9), None);
+
+    // Nothing below a modal scene, even outside its zone
+    stack.push(3, Box::new(ZoneScene::new(200, 200, 10, 10, true, EventKind::Pointer)), 1);
+    assert_eq!(stack.drop_target_at(10, 10, &card, 9), None);
+  }
+
+  #[test]
+  fn test_focus_order() {
+    let mut bus = test_bus();
+    let mut stack = SceneStack::new(Box::new(TestScene::new()), NodeId::new(1));
+    stack.push(1, Box::new(FocusableScene { modal: false }), 1); // id 2
+    stack.push(1, Box::new(FocusableScene { modal: false }), 1); // id 3
+    stack.push(2, Box::new(FocusableScene { modal: false }), 1); // id 4
+    stack.push(2, Box::new(TestScene::new()), 1); // id 5, not focusable
+    stack.set_nodeid(2, NodeId::new(20));
+    stack.set_nodeid(3, NodeId::new(10));
+    stack.refresh_focus(&mut bus);
+
+    let rank = |node: NodeId| Some(u64::from(node) as usize);
+    // Layout order first, then the stack order
+    assert_eq!(stack.focus_order(rank), vec![3, 2, 4]);
+    assert!(!stack.can_focus(5));
+
+    stack.set_flags(2, SceneFlags::Hidden, true);
+    stack.set_flags(4, SceneFlags::InputDisabled, true);
+    stack.refresh_focus(&mut bus);
+    assert_eq!(stack.focus_order(rank), vec![3]);
+
+    // Nothing below a modal scene
+    stack.push(3, Box::new(FocusableScene { modal: true }), 1); // id 6
+    stack.refresh_focus(&mut bus);
+    assert_eq!(stack.focus_order(rank), vec![6]);
+    assert!(!stack.can_focus(3));
+  }
+
+  #[test]
+  fn test_hit_shape() {
+    let mut stack = SceneStack::new(Box::new(TestScene::new()), NodeId::new(1));
+    stack.push(1, Box::new(ZoneScene::new(0, 0, 100, 100, false, EventKind::Pointer)), 1); // id 2
+    stack.push(2, Box::new(RoundScene {}), 1); // id 3
+
+    assert_eq!(stack.scene_at(50, 50, &EventKind::Pointer), Some(3));
+    assert_eq!(stack.scene_at(41, 41, &EventKind::Pointer), Some(2)); // Transparent corner of the round scene
+    assert_eq!(stack.zone(3), Some(Rect::new(40, 40, 20, 20)));
+  }
+
+  #[test]
+  fn test_route() {
+    let journal = Rc::new(RefCell::new(Vec::new()));
+    let mut bus = test_bus();
+    let mut stack = SceneStack::new(Box::new(TestScene::new()), NodeId::new(1));
+    let routed = |name, zone, subs, stop_in| Box::new(RoutedScene { name, zone, subs, stop_in, journal: Rc::clone(&journal) });
+    // A list (id 2) with a row (id 3) which doesn't subscribe to the clicks
+    stack.push(1, routed("list", Some(Rect::new(0, 0, 100, 100)), EventKind::SdlMouseClick, Some(Phase::Bubble)), 1);
+    stack.push(2, routed("row", Some(Rect::new(0, 0, 100, 10)), EventKind::NotAnEvent, None), 2);
+    let click = |x, y| Action::SdlEvent(Event::MouseButtonDown { timestamp: 0, window_id: 0, which: 0, 
+      mouse_btn: sdl2::mouse::MouseButton::Left, clicks: 1, x, y });
+
+    // Capture then bubble on the list, which stops it
+    assert_eq!(stack.propagate_sdl2_to_subscribers(&mut bus, click(5, 5), EventKind::SdlMouseClick), Some(2));
+    assert_eq!(*journal.borrow(), vec!["list Capture 3", "list Bubble 3"]);
+    journal.borrow_mut().clear();
+
+    // Clicked on the list itself
+    assert_eq!(stack.propagate_sdl2_to_subscribers(&mut bus, click(5, 50), EventKind::SdlMouseClick), None);
+    assert_eq!(*journal.borrow(), vec!["list Target 2"]);
+    journal.borrow_mut().clear();
+
+    // A subscribed cell in the row stops it in the target phase : no bubbling
+    stack.push(3, routed("cell", Some(Rect::new(0, 0, 10, 10)), EventKind::SdlMouseClick, Some(Phase::Target)), 3); // id 4
+    assert_eq!(stack.propagate_sdl2_to_subscribers(&mut bus, click(5, 5), EventKind::SdlMouseClick), Some(4));
+    assert_eq!(*journal.borrow(), vec!["list Capture 4", "cell Target 4"]);
+    journal.borrow_mut().clear();
+
+    // Nobody stops it : the list saw it routed, not again in the layer walk
+    stack.push(1, routed("grid", Some(Rect::new(200, 0, 100, 100)), EventKind::SdlMouseClick, None), 1); // id 5
+    stack.push(5, routed("tile", Some(Rect::new(200, 0, 10, 10)), EventKind::SdlMouseClick, None), 5); // id 6
+    assert_eq!(stack.propagate_sdl2_to_subscribers(&mut bus, click(205, 5), EventKind::SdlMouseClick), None);
+    assert_eq!(*journal.borrow(), vec!["grid Capture 6", "tile Target 6", "grid Bubble 6"]);
+  }
 }
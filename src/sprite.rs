@@ -1,25 +1,116 @@
+use crate::animation::{AnimationClip, AnimationMode};
+use crate::atlas::{self, AtlasFormat};
 use crate::errors::*;
 use crate::font::FontStore;
+use crate::packer::{RuntimeAtlas, PAGE_SIZE};
+use crate::preload::PreloadJob;
+use crate::scene::SceneID;
 use crate::texture::TextureStore;
 use crate::infraglobals;
+use crate::vfs;
 
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::{TextureCreator, WindowCanvas};
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, Texture, TextureCreator, WindowCanvas};
 use sdl2::surface::Surface;
 use sdl2::video::WindowContext;
 use tracing::debug;
-use std::cell::Cell;
-use std::path::PathBuf;
+use tracing::warn;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::rc::Rc;
+use std::time::Instant;
+
+// A sprite of the store. Get it by name (SpriteStore::id_of, ActionBus::sprite) rather than by its
+// position in spritedesc.json, or generate the consts at build time (cf. generate_sprite_consts).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpriteHandle(pub usize);
+
+impl SpriteHandle {
+  pub fn id(&self) -> usize {
+    self.0
+  }
+}
+
+// The raw ids (e.g. HamID::SpriteID of the texts) still work
+impl From<usize> for SpriteHandle {
+  fn from(id: usize) -> Self {
+    SpriteHandle(id)
+  }
+}
+
+// How to draw a sprite (cf. Renderer::render_sprite). The default : native size, as is.
+// e.g. DrawParams { size: Some((w, h)), flip_h: true, ..Default::default() }
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DrawParams {
+  pub size: Option<(u32, u32)>, // Destination size, wins over scale
+  pub scale: Option<f32>,
+  pub angle: f64, // Degrees, clockwise
+  pub pivot: Option<(i32, i32)>, // Rotation center, relative to the destination rect. None : its center
+  pub flip_h: bool,
+  pub flip_v: bool,
+  pub tint: Option<Color>, // Color modulation (the alpha of the color is ignored)
+  pub alpha: Option<u8>,
+  pub blend: Option<BlendMode>, // None : the texture one (Blend if alpha is set)
+}
+
+impl DrawParams {
+  // Stretched to w x h, e.g. to fill the layout rect
+  pub fn sized(w: u32, h: u32) -> Self {
+    Self { size: Some((w, h)), ..Default::default() }
+  }
+
+  pub fn dest_rect(&self, x: i32, y: i32, src_w: u32, src_h: u32) -> Rect {
+    let (w, h) = match (self.size, self.scale) {
+      (Some(size), _) => size,
+      (None, Some(scale)) => ((src_w as f32 * scale).round() as u32, (src_h as f32 * scale).round() as u32),
+      (None, None) => (src_w, src_h)
+    };
+    Rect::new(x, y, w, h)
+  }
+}
+
+// The former render_sprite(id, x, y, alpha) calls
+impl From<Option<u8>> for DrawParams {
+  fn from(alpha: Option<u8>) -> Self {
+    Self { alpha, ..Default::default() }
+  }
+}
+
+// What can be looked up by name : the sprites and the animation clips
+#[derive(Default)]
+pub struct SpriteCatalog {
+  names: HashMap<String, SpriteHandle>,
+  clips: HashMap<String, Rc<AnimationClip>>,
+}
+
+impl SpriteCatalog {
+  pub fn sprite(&self, name: &str) -> Option<SpriteHandle> {
+    self.names.get(name).copied()
+  }
+
+  pub fn clip(&self, name: &str) -> Option<Rc<AnimationClip>> {
+    self.clips.get(name).cloned()
+  }
+}
+
+pub type SharedCatalog = Rc<RefCell<SpriteCatalog>>;
 
 pub struct SpriteStore<'a>
 {
   store: Vec<Sprite>,
   current_len: Rc<Cell<usize>>,
+  catalog: SharedCatalog, // Shared with the ActionBus, so that the scenes can look the names up in update()
   texture_store: TextureStore<'a>,
+  atlas: RuntimeAtlas, // The pages shared by the texts and the "packed" sheets
+  packed_sheets: HashMap<String, (usize, Rect)>, // File -> page, place in it
+  text_owners: HashMap<SceneID, Vec<usize>>, // The text sprites of the scenes, freed with them
+  text_sources: HashMap<usize, (String, String)>, // Text sprite -> font, text (hot reloading)
+  freed_reported: HashSet<usize>, // The freed text sprites some scene still tried to draw
 
-  cached_text: Option<Surface<'a>>
+  cached_text: Option<Surface<'a>>,
+  cached_text_source: Option<(String, String)>,
 }
 
 impl<'a> SpriteStore<'a>
@@ -28,7 +119,7 @@ impl<'a> SpriteStore<'a>
   pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self 
   {
     let json_path = infraglobals::get_conf_path().join("spritedesc.json");
-    if !json_path.exists() {
+    if !vfs::exists(&json_path) {
       prompt_err_and_panic(&format!("SpriteStore Config file not found: {:?}", json_path), "", None)
     }
 
@@ -36,31 +127,212 @@ impl<'a> SpriteStore<'a>
     let v_jsheets = load_sprites_from_json(&json_path);
     let mut texture_store = TextureStore::new(texture_creator, v_jsheets.len());
 
-    // Fill in the sprite store with sprites pointing to empty textures (no surface loading)
+    // Fill in the sprite store with sprites pointing to empty textures (no surface loading),
+    // except for the "packed" sheets, copied into the atlas pages right away
     let mut store = Vec::new();
-    for jsheet in v_jsheets { // 1 sheet == 1 texture == N sprites
-      let tex_id = texture_store.push_new_texture(jsheet.file, None);
-      for js in jsheet.sprites { // js is the json representation of a sprite
-        debug!(target: "hg::sprite", "Sprite {} made alone ? {:?}", js.name, js.make_alone);
-        store.push(Sprite::new(Rect::new(js.x, js.y, js.w, js.h), tex_id));
+    let mut names = HashMap::new();
+    let mut atlas = RuntimeAtlas::new();
+    let mut packed_sheets = HashMap::new();
+    let jclips = merge_desc(&mut store, &mut names, v_jsheets, false, |jsheet| {
+      if jsheet.packed && let Some((tex_id, rect)) = pack_sheet(&mut texture_store, &mut atlas, &mut packed_sheets, &jsheet.file) {
+        return (tex_id, (rect.x(), rect.y()));
       }
-    }
+      (texture_store.push_new_texture(jsheet.file.clone(), None), (0, 0))
+    });
+
+    // The frames may come from any sheet
+    let clips = load_clips(jclips, &names);
     let cur_len: usize = store.len();
-    SpriteStore { store, current_len: Rc::new(Cell::new(cur_len)), texture_store, cached_text: None }
+    SpriteStore { 
+      store, current_len: Rc::new(Cell::new(cur_len)), 
+      catalog: Rc::new(RefCell::new(SpriteCatalog { names, clips })), 
+      texture_store, atlas, packed_sheets, text_owners: HashMap::new(), text_sources: HashMap::new(), freed_reported: HashSet::new(),
+      cached_text: None, cached_text_source: None 
+    }
+  }
+
+  // Hot reloading : spritedesc.json (or an imported atlas) changed. The named sprites keep their ids,
+  // the new ones are appended. A broken file is just reported.
+  pub(crate) fn reload_desc(&mut self) {
+    let json_path = infraglobals::get_conf_path().join("spritedesc.json");
+    let desc = match try_load_sprites_from_json(&json_path) {
+      Ok(desc) => desc,
+      Err(e) => {
+        warn!(target: "hg::sprite", "spritedesc.json not reloaded: {}", e);
+        return;
+      }
+    };
+    let mut names = self.catalog.borrow().names.clone();
+    let (texture_store, atlas, packed_sheets) = (&mut self.texture_store, &mut self.atlas, &mut self.packed_sheets);
+    let jclips = merge_desc(&mut self.store, &mut names, desc, true, |jsheet| {
+      if jsheet.packed && let Some((tex_id, rect)) = pack_sheet(texture_store, atlas, packed_sheets, &jsheet.file) {
+        return (tex_id, (rect.x(), rect.y()));
+      }
+      let file = &jsheet.file;
+      (texture_store.id_of_file(file).unwrap_or_else(|| texture_store.push_new_texture(file.clone(), None)), (0, 0))
+    });
+    let clips = load_clips(jclips, &names);
+    self.current_len.set(self.store.len());
+    *self.catalog.borrow_mut() = SpriteCatalog { names, clips };
+  }
+
+  // Hot reloading : an image of the img folder changed. It is decoded again into its texture slot,
+  // a packed sheet is copied again (and its sprites moved if it doesn't fit in its old place anymore).
+  // An image which cannot be decoded (e.g. still being written) keeps the old texture.
+  pub(crate) fn reload_image(&mut self, file: &Path) {
+    if let Some(tex_id) = self.texture_store.id_of_path(file) {
+      match vfs::load_surface(&infraglobals::get_img_path().join(file)) {
+        Ok(surface) => self.texture_store.replace(tex_id, surface),
+        Err(e) => warn!(target: "hg::sprite", "{:?} not reloaded: {}", file, e)
+      }
+    }
+    let Some((key, old)) = self.packed_sheets.iter().find(|(f, _)| Path::new(f) == file).map(|(f, p)| (f.clone(), *p))
+      else { return; };
+    let new = pack_sheet(&mut self.texture_store, &mut self.atlas, &mut self.packed_sheets, &key);
+    if new.is_some_and(|new| new != old) {
+      self.reload_desc();
+    }
+  }
+
+  // Hot reloading : the fonts were loaded again, so are the texts
+  pub(crate) fn rerender_texts(&mut self, font_store: &FontStore) {
+    let sources: Vec<(usize, Surface)> = self.text_sources.iter()
+      .map(|(sprite_id, (font_name, text))| (*sprite_id, render_text(font_store, font_name, text))).collect();
+    for (sprite_id, surface) in sources {
+      let tex_id = self.store[sprite_id].texture_id;
+      if self.atlas.is_page(tex_id) {
+        self.atlas.release(tex_id);
+        let (tex_id, src_rect) = place_surface(&mut self.texture_store, &mut self.atlas, surface);
+        self.store[sprite_id] = Sprite::new(src_rect, tex_id);
+      }
+      else {
+        self.store[sprite_id].src_rect = Rect::new(0, 0, surface.width(), surface.height());
+        self.texture_store.replace(tex_id, surface);
+      }
+    }
+  }
+
+  pub fn id_of(&self, name: &str) -> Option<SpriteHandle> {
+    self.catalog.borrow().sprite(name)
   }
 
-  pub fn render(&mut self, canvas: &mut WindowCanvas, sprite_id: usize, x: i32, y: i32, alpha: Option<u8>) {
+  // An animation clip of spritedesc.json, for an AnimationPlayer
+  pub fn clip(&self, name: &str) -> Option<Rc<AnimationClip>> {
+    self.catalog.borrow().clip(name)
+  }
+
+  pub fn shared_catalog(&self) -> SharedCatalog {
+    Rc::clone(&self.catalog)
+  }
+
+  pub fn render(&mut self, canvas: &mut WindowCanvas, sprite: SpriteHandle, x: i32, y: i32, params: &DrawParams) {
+    if self.is_freed(sprite) {
+      return;
+    }
     // Find the sprite metadata in the registry
-    let sprite = &self.store[sprite_id];
+    let sprite = &self.store[sprite.id()];
+    let src_rect = sprite.src_rect;
+    let place = sprite.placement(x, y, params);
     // If the texture hasn't been set yet, load it now
     // It may not be set as the texture of that sprite but the texture may have been loaded before!
-    
-    if let Some(alph) = alpha {
-      self.texture_store.set_alpha(sprite.texture_id, alph);
-    } 
-    let tex = self.texture_store.get_texture(sprite.texture_id) ;
-    let dest_rect = Rect::new(x, y, sprite.src_rect.width(), sprite.src_rect.height());
-    canvas.copy(tex, sprite.src_rect, dest_rect).unwrap();
+    let tex = self.texture_store.get_texture_mut(sprite.texture_id);
+
+    if place.angle == 0.0 && !place.flip_h && !place.flip_v {
+      modulated(tex, params, |tex| canvas.copy(tex, src_rect, place.dest).unwrap());
+    }
+    else {
+      modulated(tex, params, |tex| 
+        canvas.copy_ex(tex, src_rect, place.dest, place.angle, place.pivot, place.flip_h, place.flip_v).unwrap());
+    }
+  }
+
+  // Draws the sprite into dest, keeping its corners intact (cf. "nine_slice" in spritedesc.json).
+  // A sprite without insets is just stretched. Only the tint, alpha and blend of params are used.
+  // Not for the rotated or trimmed frames of an imported atlas.
+  pub fn render_nine_slice(&mut self, canvas: &mut WindowCanvas, sprite: SpriteHandle, dest: Rect, params: &DrawParams) {
+    if self.is_freed(sprite) {
+      return;
+    }
+    let sprite = &self.store[sprite.id()];
+    let pieces = match &sprite.nine_slice {
+      Some(insets) => nine_slice_pieces(sprite.src_rect, insets, dest),
+      None => vec![(sprite.src_rect, dest)]
+    };
+    let tex = self.texture_store.get_texture_mut(sprite.texture_id);
+    modulated(tex, params, |tex| {
+      for (src, dst) in pieces {
+        canvas.copy(tex, src, dst).unwrap();
+      }
+    });
+  }
+
+  // A freed text is not rendered. Reported once, not at every frame it is drawn.
+  fn is_freed(&mut self, sprite: SpriteHandle) -> bool {
+    if !self.store[sprite.id()].freed {
+      return false;
+    }
+    if self.freed_reported.insert(sprite.id()) {
+      warn!(target: "hg::sprite", "Sprite {} not rendered: the text was freed with its scene", sprite.id());
+    }
+    true
+  }
+
+  // The sheet texture is freed (e.g. at the end of a level) and reloaded if a sprite uses it again
+  pub fn unload_sheet(&mut self, file: &str) -> bool {
+    match self.texture_store.id_of_file(file) {
+      Some(tex_id) => self.texture_store.unload(tex_id),
+      None => {
+        warn!(target: "hg::sprite", "unload_sheet: unknown sheet {}", file);
+        false
+      }
+    }
+  }
+
+  // Bytes of textures before the least recently used sheets are unloaded. None : no limit
+  pub fn set_texture_budget(&mut self, budget: Option<usize>) {
+    self.texture_store.set_budget(budget);
+  }
+
+  pub fn texture_memory(&self) -> usize {
+    self.texture_store.used_bytes()
+  }
+
+  // Action::Preload : starts decoding the sheets (all of them if sheets is empty) on a worker thread
+  pub(crate) fn start_preload(&self, owner: SceneID, sheets: &[String]) -> PreloadJob {
+    let (files, already) = self.texture_store.preload_list(sheets);
+    PreloadJob::start(owner, files, already)
+  }
+
+  // Uploads the decoded images until the deadline (at least one)
+  pub(crate) fn upload_preloaded(&mut self, job: &mut PreloadJob, deadline: Instant) {
+    while let Some((texture_id, image)) = job.next_decoded() {
+      self.texture_store.insert_decoded(texture_id, image);
+      if Instant::now() >= deadline {
+        break;
+      }
+    }
+  }
+
+  // The scenes died : their texts won't be rendered anymore
+  pub(crate) fn free_texts_of(&mut self, scenes: &HashSet<SceneID>) {
+    for id in scenes {
+      for sprite_id in self.text_owners.remove(id).unwrap_or_default() {
+        self.store[sprite_id].freed = true;
+        let tex_id = self.store[sprite_id].texture_id;
+        if self.atlas.is_page(tex_id) {
+          self.atlas.release(tex_id); // The page is reused once all its texts are gone
+        }
+        else {
+          self.texture_store.unload(tex_id);
+        }
+        self.text_sources.remove(&sprite_id);
+      }
+    }
+  }
+
+  // The runtime atlas pages made so far (texts and packed sheets)
+  pub fn atlas_pages(&self) -> usize {
+    self.atlas.page_count()
   }
 
   pub fn shared_len(&self) -> Rc<Cell<usize>> {
@@ -68,11 +340,10 @@ impl<'a> SpriteStore<'a>
   }
 
   pub fn try_ttf_texture(&mut self, font_store: &FontStore, font_name: &str, text: String, max_width: u32) -> (u32, u32) {
-    let font = font_store.get(font_name); 
-      
     // Render text to a surface, and convert surface to a texture
     // TODO 
-    self.cached_text = Some(font.render(&text).blended(Color::RGB(0, 0, 50)).unwrap());// TODO color custo 
+    self.cached_text = Some(render_text(font_store, font_name, &text));
+    self.cached_text_source = Some((font_name.to_owned(), text));
 
     let w = self.cached_text.as_ref().unwrap().width();
     let h = self.cached_text.as_ref().unwrap().height();
@@ -81,13 +352,18 @@ impl<'a> SpriteStore<'a>
     (w, h)
   }
 
-  pub fn commit_ttf_texture(&mut self) -> usize {
-    if let Some(cached_text) = self.cached_text.as_ref() {
-      let width = cached_text.width();
-      let height = cached_text.height();
-      let tex_id = self.texture_store.push_new_texture("".to_owned(), self.cached_text.take());
-      self.store.push(Sprite::new(Rect::new(0, 0, width, height), tex_id));
+  // owner : the text is freed when that scene dies (cf. free_texts_of).
+  // A small text goes into a runtime atlas page (shared with other texts), a big one gets its own texture.
+  pub fn commit_ttf_texture(&mut self, owner: SceneID) -> usize {
+    if let Some(cached_text) = self.cached_text.take() {
+      let (tex_id, src_rect) = place_surface(&mut self.texture_store, &mut self.atlas, cached_text);
+      self.store.push(Sprite::new(src_rect, tex_id));
       self.current_len.set(self.current_len.get() + 1);
+      let sprite_id = self.store.len() - 1;
+      self.text_owners.entry(owner).or_default().push(sprite_id);
+      if let Some(source) = self.cached_text_source.take() {
+        self.text_sources.insert(sprite_id, source);
+      }
       return tex_id;
     } else {
       prompt_err_and_panic("commit_ttf_texture failed: cached_text is None", "", None);
@@ -95,16 +371,214 @@ impl<'a> SpriteStore<'a>
   }
 }
 
+// Into an atlas page if small enough (and if the page upload works), else its own texture
+fn place_surface(texture_store: &mut TextureStore, atlas: &mut RuntimeAtlas, surface: Surface) -> (usize, Rect) {
+  let (w, h) = (surface.width(), surface.height());
+  if let Some((tex_id, rect)) = atlas.place(w, h, || texture_store.push_atlas_page(PAGE_SIZE)) {
+    match texture_store.blit(tex_id, rect, &surface) {
+      Ok(()) => return (tex_id, rect),
+      Err(e) => {
+        warn!(target: "hg::sprite", "Cannot copy a {}x{} surface into the atlas page {}: {}", w, h, tex_id, e);
+        atlas.release(tex_id);
+      }
+    }
+  }
+  let tex_id = texture_store.push_new_texture("".to_owned(), Some(surface));
+  (tex_id, Rect::new(0, 0, w, h))
+}
+
+// The image of a "packed" sheet copied into an atlas page : the page and where. Loaded again, it keeps
+// its place if its size didn't change. None if the image cannot be loaded.
+fn pack_sheet(texture_store: &mut TextureStore, atlas: &mut RuntimeAtlas, packed_sheets: &mut HashMap<String, (usize, Rect)>, 
+              file: &str) -> Option<(usize, Rect)> {
+  let surface = vfs::load_surface(&infraglobals::get_img_path().join(file))
+    .map_err(|e| warn!(target: "hg::sprite", "Cannot pack the sheet {}: {}", file, e)).ok()?;
+  if let Some(&(tex_id, rect)) = packed_sheets.get(file) {
+    if rect.size() == surface.size() && texture_store.blit(tex_id, rect, &surface).is_ok() {
+      return Some((tex_id, rect));
+    }
+    atlas.release(tex_id);
+  }
+  let placed = place_surface(texture_store, atlas, surface);
+  packed_sheets.insert(file.to_owned(), placed);
+  Some(placed)
+}
+
+fn render_text<'f>(font_store: &FontStore, font_name: &str, text: &str) -> Surface<'f> {
+  let font = font_store.get(font_name); 
+  font.render(text).blended(Color::RGB(0, 0, 50)).unwrap() // TODO color custo 
+}
+
+// Adds the sheets to the store. When reloading, a sprite whose name is known takes its old slot
+// (and an unnamed one is ignored). tex_id_of : the texture of a sheet, and where the sheet is in it
+// (not at 0, 0 for a packed sheet). Returns the clips to load.
+fn merge_desc(store: &mut Vec<Sprite>, names: &mut HashMap<String, SpriteHandle>, desc: SpriteDescJsonRep, 
+              reloading: bool, mut tex_id_of: impl FnMut(&SpriteSheetJsonRep) -> (usize, (i32, i32))) -> Vec<ClipJsonRep> {
+  let mut seen = HashSet::new();
+  let mut jclips = Vec::new();
+  for jsheet in desc { // 1 sheet == 1 texture == N sprites
+    let (tex_id, (dx, dy)) = tex_id_of(&jsheet);
+    for js in jsheet.sprites { // js is the json representation of a sprite
+      debug!(target: "hg::sprite", "Sprite {} made alone ? {:?}", js.name, js.make_alone);
+      let mut sprite = Sprite::from_json(&js, tex_id);
+      sprite.src_rect.offset(dx, dy);
+      let first = seen.insert(js.name.clone());
+      match names.get(&js.name) {
+        Some(handle) if reloading && first && !js.name.is_empty() => store[handle.id()] = sprite,
+        _ if reloading && js.name.is_empty() => {
+          warn!(target: "hg::sprite", "An unnamed sprite of {} cannot be reloaded", jsheet.file);
+        },
+        _ => {
+          store.push(sprite);
+          add_name(names, js.name, store.len() - 1);
+        }
+      }
+    }
+    jclips.extend(jsheet.animations);
+  }
+  jclips
+}
+
+// The texture is shared by the sprites of the sheet : the modulation is only for this drawing
+fn modulated(tex: &mut Texture, params: &DrawParams, draw: impl FnOnce(&Texture)) {
+  let saved = (tex.alpha_mod(), tex.color_mod(), tex.blend_mode());
+  if let Some(alpha) = params.alpha {
+    tex.set_alpha_mod(alpha);
+    tex.set_blend_mode(BlendMode::Blend);
+  }
+  if let Some(tint) = params.tint {
+    tex.set_color_mod(tint.r, tint.g, tint.b);
+  }
+  if let Some(blend) = params.blend {
+    tex.set_blend_mode(blend);
+  }
+
+  draw(tex);
+
+  tex.set_alpha_mod(saved.0);
+  tex.set_color_mod(saved.1.0, saved.1.1, saved.1.2);
+  tex.set_blend_mode(saved.2);
+}
+
+// Nine-slice insets, in sprite pixels. The edges and the center are stretched, or repeated if tile.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct NineSlice {
+  pub left: u32,
+  pub top: u32,
+  pub right: u32,
+  pub bottom: u32,
+  #[serde(default)]
+  pub tile: bool,
+}
+
+// The (source, destination) rects to copy
+fn nine_slice_pieces(src: Rect, insets: &NineSlice, dest: Rect) -> Vec<(Rect, Rect)> {
+  let cols = slice_axis(src.x(), src.width(), insets.left, insets.right, dest.x(), dest.width(), insets.tile);
+  let rows = slice_axis(src.y(), src.height(), insets.top, insets.bottom, dest.y(), dest.height(), insets.tile);
+  let mut pieces = Vec::with_capacity(cols.len() * rows.len());
+  for &(sy, sh, dy, dh) in &rows {
+    for &(sx, sw, dx, dw) in &cols {
+      pieces.push((Rect::new(sx, sy, sw, sh), Rect::new(dx, dy, dw, dh)));
+    }
+  }
+  pieces
+}
+
+// One axis : (src start, src len, dest start, dest len) segments, without the empty ones
+fn slice_axis(src: i32, src_len: u32, lo: u32, hi: u32, dst: i32, dst_len: u32, tile: bool) -> Vec<(i32, u32, i32, u32)> {
+  let (lo, hi) = (lo.min(src_len), hi.min(src_len - lo.min(src_len)));
+  // Not enough room for the borders : shrink them proportionally
+  let (dlo, dhi) = if lo + hi > dst_len {
+    let dlo = (dst_len as u64 * lo as u64 / (lo + hi) as u64) as u32;
+    (dlo, dst_len - dlo)
+  } else { (lo, hi) };
+  let (mid, dmid) = (src_len - lo - hi, dst_len - dlo - dhi);
+
+  let mut segs = vec![(src, lo, dst, dlo)];
+  if tile && mid > 0 {
+    let mut done = 0;
+    while done < dmid {
+      let len = mid.min(dmid - done); // The last one is cropped
+      segs.push((src + lo as i32, len, dst + (dlo + done) as i32, len));
+      done += len;
+    }
+  }
+  else {
+    segs.push((src + lo as i32, mid, dst + dlo as i32, dmid));
+  }
+  segs.push((src + (lo + mid) as i32, hi, dst + (dlo + dmid) as i32, dhi));
+  segs.retain(|&(_, sl, _, dl)| sl > 0 && dl > 0);
+  segs
+}
+
 struct Sprite {
   src_rect: Rect,      // Source rectangle defining the sprite's portion in the texture
+  nine_slice: Option<NineSlice>,
+  rotated: bool, // The texture holds it turned 90° clockwise (src_rect is the turned one)
+  trim: Option<Trim>,
   //scenes: Vec<String>,  // Scenes where this sprite is used (TODO scenename)
+  freed: bool, // A text of a dead scene : its place in the atlas page may hold another text now
   texture_id: usize // the index of the texture path in the texture path vector
 }
 
 impl Sprite {
   pub fn new(src_rect: Rect, texture_id: usize) -> Sprite {
-    Sprite { src_rect, texture_id}
+    Sprite { src_rect, nine_slice: None, rotated: false, trim: None, freed: false, texture_id }
+  }
+
+  fn from_json(js: &SpriteJsonRep, texture_id: usize) -> Sprite {
+    // w, h : the size as displayed
+    let src_rect = if js.rotated { Rect::new(js.x, js.y, js.h, js.w) } else { Rect::new(js.x, js.y, js.w, js.h) };
+    Sprite { src_rect, nine_slice: js.nine_slice, rotated: js.rotated, trim: js.trim, freed: false, texture_id }
   }
+
+  // Where and how to copy it, for render()
+  fn placement(&self, x: i32, y: i32, params: &DrawParams) -> Placement {
+    let src = self.src_rect;
+    let (w, h) = if self.rotated { (src.height(), src.width()) } else { (src.width(), src.height()) };
+    let (sw, sh, ox, oy) = self.trim.map_or((w, h, 0, 0), |t| (t.source_w, t.source_h, t.x, t.y));
+    let logical = params.dest_rect(x, y, sw, sh); // The untrimmed sprite
+    let (fx, fy) = (logical.width() as f32 / sw.max(1) as f32, logical.height() as f32 / sh.max(1) as f32);
+    // Flipped : the trimmed part is mirrored too
+    let ox = if params.flip_h { sw as i32 - ox - w as i32 } else { ox };
+    let oy = if params.flip_v { sh as i32 - oy - h as i32 } else { oy };
+    let (dx, dy) = ((ox as f32 * fx).round() as i32, (oy as f32 * fy).round() as i32);
+    let piece = Rect::new(logical.x() + dx, logical.y() + dy, (w as f32 * fx).round() as u32, (h as f32 * fy).round() as u32);
+
+    if self.rotated {
+      // Turned back around the piece center (so the pivot is not used)
+      let dest = Rect::from_center(piece.center(), piece.height(), piece.width());
+      return Placement { dest, angle: params.angle - 90.0, pivot: None, flip_h: params.flip_v, flip_v: params.flip_h };
+    }
+    // The pivot is relative to the untrimmed sprite
+    let pivot = match (params.pivot, self.trim) {
+      (None, None) => None,
+      (pivot, _) => {
+        let (px, py) = pivot.unwrap_or((logical.width() as i32 / 2, logical.height() as i32 / 2));
+        Some(Point::new(px - dx, py - dy))
+      }
+    };
+    Placement { dest: piece, angle: params.angle, pivot, flip_h: params.flip_h, flip_v: params.flip_v }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+struct Placement {
+  dest: Rect,
+  angle: f64,
+  pivot: Option<Point>,
+  flip_h: bool,
+  flip_v: bool,
+}
+
+// A trimmed sprite (the transparent borders were cropped by the atlas tool) : where it sits in its
+// original source_w x source_h image
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Trim {
+  pub x: i32,
+  pub y: i32,
+  pub source_w: u32,
+  pub source_h: u32,
 }
 
 // Represent deserialized sprite data
@@ -112,33 +586,316 @@ use serde::Deserialize;
 
 #[derive(Deserialize, Debug)]
 pub struct SpriteJsonRep {
-  name: String,
-  x: i32,
-  y: i32,
-  w: u32,
-  h: u32,
-  make_alone: Option<bool> // The sprite should have its own texture (TODO useful ?)
+  pub(crate) name: String,
+  pub(crate) x: i32,
+  pub(crate) y: i32,
+  pub(crate) w: u32,
+  pub(crate) h: u32,
+  pub(crate) make_alone: Option<bool>, // The sprite should have its own texture (TODO useful ?)
+  pub(crate) nine_slice: Option<NineSlice>, // e.g. { "left": 8, "top": 8, "right": 8, "bottom": 8, "tile": true }
+  #[serde(default)]
+  pub(crate) rotated: bool, // Stored turned 90° clockwise, w x h being its size as displayed
+  pub(crate) trim: Option<Trim>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct SpriteSheetJsonRep {
-  file: String,
-  sprites: Vec<SpriteJsonRep>,
+  pub(crate) file: String,
+  pub(crate) sprites: Vec<SpriteJsonRep>,
+  #[serde(default)]
+  pub(crate) animations: Vec<ClipJsonRep>,
+  #[serde(default)]
+  pub(crate) packed: bool, // Small image copied into a runtime atlas page, shared with other sheets and the texts
+}
+
+// An entry of spritedesc.json : a sheet, or an atlas exported by a tool, e.g.
+// { "import": "hero.json", "format": "aseprite" } (format : texture_packer or aseprite, guessed if missing)
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum SheetEntryJsonRep {
+  Sheet(SpriteSheetJsonRep),
+  Import { import: String, format: Option<AtlasFormat> }, // Relative to the img folder
+}
+
+// e.g. { "name": "hero_walk", "frames": ["hero_walk_1", "hero_walk_2"], "frame_ms": 100, "mode": "ping_pong" }
+#[derive(Deserialize, Debug)]
+pub struct ClipJsonRep {
+  pub(crate) name: String,
+  pub(crate) frames: Vec<String>, // Sprite names, from any sheet
+  pub(crate) frame_ms: u32,
+  pub(crate) durations_ms: Option<Vec<u32>>, // Per frame, overrides frame_ms
+  #[serde(default)]
+  pub(crate) mode: AnimationMode,
+}
+
+// A clip with an unknown frame is dropped
+fn load_clips(jclips: Vec<ClipJsonRep>, names: &HashMap<String, SpriteHandle>) -> HashMap<String, Rc<AnimationClip>> {
+  let mut clips = HashMap::new();
+  for jc in jclips {
+    let frames: Option<Vec<SpriteHandle>> = jc.frames.iter().map(|f| names.get(f).copied()).collect();
+    let Some(frames) = frames.filter(|f| !f.is_empty()) else {
+      warn!(target: "hg::sprite", "Animation {} : no frames or unknown sprite in {:?}", jc.name, jc.frames);
+      continue;
+    };
+    let durations = (0..frames.len())
+      .map(|i| jc.durations_ms.as_ref().and_then(|d| d.get(i)).copied().unwrap_or(jc.frame_ms))
+      .collect();
+    clips.insert(jc.name.clone(), Rc::new(AnimationClip::new(&jc.name, frames, durations, jc.mode)));
+  }
+  clips
 }
 
 pub type SpriteDescJsonRep = Vec<SpriteSheetJsonRep>;
 
-// Deserialize sprite data from json
-use std::fs::File;
-use std::io::BufReader;
-use serde_json::from_reader;
-
-pub fn load_sprites_from_json(file_path: &PathBuf) -> SpriteDescJsonRep {
-  let file = File::open(file_path)
-    .unwrap_or_else(|err| { prompt_err_and_panic("load_sprites_from_json failed(open)", &err.to_string(), None); });
-  let reader = BufReader::new(file);
-  let sprite_data: SpriteDescJsonRep = from_reader(reader)
-    .unwrap_or_else(|err| { prompt_err_and_panic("load_sprites_from_json failed(read)", &err.to_string(), None); });
-  
-  sprite_data
+// Deserialize sprite data from json (from the archive if mounted)
+use serde_json::from_slice;
+
+// The first one wins : the others are still reachable by id
+fn add_name(names: &mut HashMap<String, SpriteHandle>, name: String, id: usize) {
+  if name.is_empty() {
+    return;
+  }
+  if names.contains_key(&name) {
+    warn!(target: "hg::sprite", "Duplicate sprite name {} (sprite {}), keeping the first one", name, id);
+    return;
+  }
+  names.insert(name, SpriteHandle(id));
+}
+
+pub fn load_sprites_from_json(file_path: &Path) -> SpriteDescJsonRep {
+  try_load_sprites_from_json(file_path)
+    .unwrap_or_else(|err| { prompt_err_and_panic("load_sprites_from_json failed", &err, None); })
+}
+
+pub fn try_load_sprites_from_json(file_path: &Path) -> Result<SpriteDescJsonRep, String> {
+  let bytes = vfs::read(file_path).map_err(|err| format!("open: {}", err))?;
+  let entries: Vec<SheetEntryJsonRep> = from_slice(&bytes).map_err(|err| format!("read: {}", err))?;
+  resolve_imports(entries, &infraglobals::get_img_path()).map_err(|err| format!("import: {}", err))
+}
+
+fn resolve_imports(entries: Vec<SheetEntryJsonRep>, img_dir: &Path) -> Result<SpriteDescJsonRep, String> {
+  entries.into_iter().map(|entry| match entry {
+    SheetEntryJsonRep::Sheet(sheet) => Ok(sheet),
+    SheetEntryJsonRep::Import { import, format } => atlas::import_atlas(img_dir, &import, format)
+  }).collect()
+}
+
+// Code generation : one `pub const HERO_IDLE: SpriteHandle = SpriteHandle(3);` per named sprite,
+// in the order SpriteStore::new gives the ids. Meant for a build script of the game, e.g. with the
+// install path in "assets" (spritedesc.json is in its conf folder, cf. infraglobals::get_conf_path) :
+//   let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("sprites.rs");
+//   hamgraph::sprite::write_sprite_consts(Path::new("assets/conf/spritedesc.json"), &out).unwrap();
+//   println!("cargo:rerun-if-changed=assets/conf/spritedesc.json");
+// and `include!(concat!(env!("OUT_DIR"), "/sprites.rs"));` in the game.
+pub fn generate_sprite_consts(desc: &SpriteDescJsonRep) -> String {
+  let mut out = String::from("// Generated from spritedesc.json, do not edit\n");
+  out.push_str("use hamgraph::sprite::SpriteHandle;\n\n");
+  let mut names = HashMap::new();
+  let mut consts = std::collections::HashSet::new();
+  for (id, js) in desc.iter().flat_map(|sheet| sheet.sprites.iter()).enumerate() {
+    if !js.name.is_empty() && !names.contains_key(&js.name) {
+      names.insert(js.name.clone(), id);
+      let const_name = sprite_const_name(&js.name);
+      if consts.insert(const_name.clone()) {
+        out.push_str(&format!("pub const {}: SpriteHandle = SpriteHandle({});\n", const_name, id));
+      }
+      else { // e.g. "hero-idle" and "hero_idle"
+        warn!(target: "hg::sprite", "Sprite {} : the const {} already exists", js.name, const_name);
+      }
+    }
+  }
+  out
+}
+
+// The imported atlases are looked for in the img folder next to the conf one
+pub fn write_sprite_consts(json_path: &Path, out_path: &Path) -> std::io::Result<()> {
+  let entries: Vec<SheetEntryJsonRep> = from_slice(&std::fs::read(json_path)?)?;
+  let img_dir = json_path.parent().and_then(Path::parent).unwrap_or(Path::new(".")).join("img");
+  let desc = resolve_imports(entries, &img_dir).map_err(std::io::Error::other)?;
+  std::fs::write(out_path, generate_sprite_consts(&desc))
+}
+
+// "hero-idle 2" -> HERO_IDLE_2, "1up" -> _1UP
+fn sprite_const_name(name: &str) -> String {
+  let mut s: String = name.chars()
+    .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+    .collect();
+  if s.starts_with(|c: char| c.is_ascii_digit()) {
+    s.insert(0, '_');
+  }
+  s
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn desc() -> SpriteDescJsonRep {
+    serde_json::from_str(r#"[
+      { "file": "hero.png", "sprites": [
+        { "name": "hero_idle", "x": 0, "y": 0, "w": 16, "h": 16 },
+        { "name": "hero-run 1", "x": 16, "y": 0, "w": 16, "h": 16 } ] },
+      { "file": "items.png", "sprites": [
+        { "name": "", "x": 0, "y": 0, "w": 8, "h": 8 },
+        { "name": "1up", "x": 8, "y": 0, "w": 8, "h": 8, "nine_slice": { "left": 2, "top": 2, "right": 2, "bottom": 2 } },
+        { "name": "hero_idle", "x": 16, "y": 0, "w": 8, "h": 8 } ] }
+    ]"#).unwrap()
+  }
+
+  #[test]
+  fn test_dest_rect() {
+    assert_eq!(DrawParams::default().dest_rect(5, 6, 16, 8), Rect::new(5, 6, 16, 8));
+    assert_eq!(DrawParams::sized(40, 20).dest_rect(5, 6, 16, 8), Rect::new(5, 6, 40, 20));
+    let scaled = DrawParams { scale: Some(2.5), ..Default::default() };
+    assert_eq!(scaled.dest_rect(0, 0, 16, 8), Rect::new(0, 0, 40, 20));
+    let both = DrawParams { scale: Some(2.0), ..DrawParams::sized(10, 10) };
+    assert_eq!(both.dest_rect(0, 0, 16, 8), Rect::new(0, 0, 10, 10)); // size wins
+    assert_eq!(DrawParams::from(Some(128)).alpha, Some(128));
+  }
+
+  #[test]
+  fn test_nine_slice() {
+    let insets = NineSlice { left: 4, top: 4, right: 4, bottom: 4, tile: false };
+    let pieces = nine_slice_pieces(Rect::new(100, 0, 16, 16), &insets, Rect::new(10, 20, 100, 50));
+    assert_eq!(pieces.len(), 9);
+    assert_eq!(pieces[0], (Rect::new(100, 0, 4, 4), Rect::new(10, 20, 4, 4))); // Intact corners
+    assert_eq!(pieces[2], (Rect::new(112, 0, 4, 4), Rect::new(106, 20, 4, 4)));
+    assert_eq!(pieces[4], (Rect::new(104, 4, 8, 8), Rect::new(14, 24, 92, 42))); // Stretched center
+    assert_eq!(pieces[8], (Rect::new(112, 12, 4, 4), Rect::new(106, 66, 4, 4)));
+
+    // Tiled : 92 = 11 * 8 + 4, the last tile is cropped
+    let tiled = NineSlice { tile: true, ..insets };
+    let cols = slice_axis(100, 16, 4, 4, 10, 100, true);
+    assert_eq!(cols.len(), 2 + 12);
+    assert_eq!(cols[12], (104, 4, 102, 4));
+    assert_eq!(nine_slice_pieces(Rect::new(100, 0, 16, 16), &tiled, Rect::new(10, 20, 100, 50)).len(), 14 * 8);
+
+    // Smaller than the borders : no center, the corners shrink
+    assert_eq!(slice_axis(0, 16, 4, 12, 0, 8, false), vec![(0, 4, 0, 2), (4, 12, 2, 6)]);
+  }
+
+  #[test]
+  fn test_load_clips() {
+    let jclips: Vec<ClipJsonRep> = serde_json::from_str(r#"[
+      { "name": "walk", "frames": ["a", "b", "a"], "frame_ms": 100, "durations_ms": [50], "mode": "once" },
+      { "name": "broken", "frames": ["a", "nope"], "frame_ms": 100 },
+      { "name": "empty", "frames": [], "frame_ms": 100 }
+    ]"#).unwrap();
+    let names = HashMap::from([("a".to_owned(), SpriteHandle(4)), ("b".to_owned(), SpriteHandle(7))]);
+    let clips = load_clips(jclips, &names);
+    assert_eq!(clips.len(), 1);
+    let walk = &clips["walk"];
+    assert_eq!(walk.frames(), &[SpriteHandle(4), SpriteHandle(7), SpriteHandle(4)]);
+    assert_eq!(walk.mode(), AnimationMode::Once);
+    assert_eq!(walk.duration(), 0.25);
+  }
+
+  #[test]
+  fn test_placement() {
+    let plain = Sprite::new(Rect::new(0, 0, 16, 8), 0);
+    let place = plain.placement(10, 20, &DrawParams::default());
+    assert_eq!(place, Placement { dest: Rect::new(10, 20, 16, 8), angle: 0.0, pivot: None, flip_h: false, flip_v: false });
+
+    // 28x30 cropped out of 32x32, at (2, 1)
+    let mut trimmed = Sprite::new(Rect::new(0, 0, 28, 30), 0);
+    trimmed.trim = Some(Trim { x: 2, y: 1, source_w: 32, source_h: 32 });
+    assert_eq!(trimmed.placement(10, 20, &DrawParams::default()).dest, Rect::new(12, 21, 28, 30));
+    let twice = trimmed.placement(10, 20, &DrawParams::sized(64, 64));
+    assert_eq!(twice.dest, Rect::new(14, 22, 56, 60));
+    assert_eq!(twice.pivot, Some(Point::new(28, 30))); // Still the center of the 64x64 sprite
+    let flipped = trimmed.placement(10, 20, &DrawParams { flip_h: true, ..Default::default() });
+    assert_eq!(flipped.dest, Rect::new(12, 21, 28, 30)); // 32 - 2 - 28 = 2
+    let mut right = Sprite::new(Rect::new(0, 0, 28, 30), 0);
+    right.trim = Some(Trim { x: 4, y: 0, source_w: 32, source_h: 32 });
+    let flipped = right.placement(0, 0, &DrawParams { flip_h: true, ..Default::default() });
+    assert_eq!(flipped.dest, Rect::new(0, 0, 28, 30));
+
+    // 20x30 as displayed, 30x20 in the sheet
+    let js: SpriteJsonRep = serde_json::from_str(r#"{ "name": "r", "x": 40, "y": 0, "w": 20, "h": 30, "rotated": true }"#).unwrap();
+    let rotated = Sprite::from_json(&js, 0);
+    assert_eq!(rotated.src_rect, Rect::new(40, 0, 30, 20));
+    let place = rotated.placement(0, 0, &DrawParams { flip_h: true, ..Default::default() });
+    assert_eq!(place.dest, Rect::new(-5, 5, 30, 20)); // Turned -90° around (10, 15) : covers (0, 0, 20, 30)
+    assert_eq!((place.angle, place.flip_h, place.flip_v), (-90.0, false, true));
+  }
+
+  #[test]
+  fn test_sheet_entries() {
+    let entries: Vec<SheetEntryJsonRep> = serde_json::from_str(r#"[
+      { "file": "hero.png", "sprites": [] },
+      { "import": "missing.json", "format": "aseprite" }
+    ]"#).unwrap();
+    assert!(matches!(&entries[0], SheetEntryJsonRep::Sheet(sheet) if sheet.file == "hero.png"));
+    assert!(matches!(&entries[1], SheetEntryJsonRep::Import { format: Some(AtlasFormat::Aseprite), .. }));
+    let err = resolve_imports(entries, Path::new("/nonexistent")).err().unwrap();
+    assert!(err.starts_with("missing.json"));
+  }
+
+  #[test]
+  fn test_merge_desc() {
+    let mut store = Vec::new();
+    let mut names = HashMap::new();
+    let mut files = Vec::new();
+    merge_desc(&mut store, &mut names, desc(), false, |s| { files.push(s.file.clone()); (files.len() - 1, (0, 0)) });
+    assert_eq!(store.len(), 5);
+    assert_eq!(names.len(), 3);
+
+    // Reordered, hero_idle moved to the other sheet, a new sprite
+    let reloaded: SpriteDescJsonRep = serde_json::from_str(r#"[
+      { "file": "items.png", "sprites": [
+        { "name": "new", "x": 0, "y": 0, "w": 8, "h": 8 },
+        { "name": "hero_idle", "x": 1, "y": 2, "w": 3, "h": 4 },
+        { "name": "", "x": 0, "y": 0, "w": 8, "h": 8 } ] },
+      { "file": "hero.png", "sprites": [
+        { "name": "1up", "x": 8, "y": 0, "w": 9, "h": 9 } ] }
+    ]"#).unwrap();
+    merge_desc(&mut store, &mut names, reloaded, true, |s| (files.iter().position(|known| *known == s.file).unwrap(), (0, 0)));
+    assert_eq!(store.len(), 6); // + "new" only
+    assert_eq!(names["hero_idle"], SpriteHandle(0));
+    assert_eq!((store[0].src_rect, store[0].texture_id), (Rect::new(1, 2, 3, 4), 1));
+    assert_eq!((store[3].src_rect, store[3].texture_id), (Rect::new(8, 0, 9, 9), 0));
+    assert_eq!(names["new"], SpriteHandle(5));
+    assert_eq!(names["hero-run 1"], SpriteHandle(1)); // Gone from the file, still there
+  }
+
+  #[test]
+  fn test_merge_packed() {
+    let desc: SpriteDescJsonRep = serde_json::from_str(r#"[
+      { "file": "coin.png", "packed": true, "sprites": [
+        { "name": "coin", "x": 0, "y": 0, "w": 16, "h": 16 },
+        { "name": "coin_shine", "x": 16, "y": 0, "w": 16, "h": 16 } ] },
+      { "file": "hero.png", "sprites": [ { "name": "hero", "x": 4, "y": 0, "w": 8, "h": 8 } ] }
+    ]"#).unwrap();
+    let mut store = Vec::new();
+    let mut names = HashMap::new();
+    // coin.png at 100, 40 in the page 3
+    merge_desc(&mut store, &mut names, desc, false, |s| if s.packed { (3, (100, 40)) } else { (0, (0, 0)) });
+    assert_eq!((store[0].src_rect, store[0].texture_id), (Rect::new(100, 40, 16, 16), 3));
+    assert_eq!(store[1].src_rect, Rect::new(116, 40, 16, 16));
+    assert_eq!((store[2].src_rect, store[2].texture_id), (Rect::new(4, 0, 8, 8), 0));
+  }
+
+  #[test]
+  fn test_sprite_names() {
+    let mut names = HashMap::new();
+    for (id, js) in desc().into_iter().flat_map(|sheet| sheet.sprites).enumerate() {
+      add_name(&mut names, js.name, id);
+    }
+    assert_eq!(names.len(), 3);
+    assert_eq!(names.get("hero_idle"), Some(&SpriteHandle(0))); // Not the duplicate
+    assert_eq!(names.get("1up"), Some(&SpriteHandle(3)));
+    assert_eq!(names.get(""), None);
+  }
+
+  #[test]
+  fn test_generate_sprite_consts() {
+    let code = generate_sprite_consts(&desc());
+    assert!(code.contains("pub const HERO_IDLE: SpriteHandle = SpriteHandle(0);\n"));
+    assert!(code.contains("pub const HERO_RUN_1: SpriteHandle = SpriteHandle(1);\n"));
+    assert!(code.contains("pub const _1UP: SpriteHandle = SpriteHandle(3);\n"));
+    assert_eq!(code.matches("pub const").count(), 3);
+  }
 }
@@ -1,13 +1,29 @@
+use std::path::{Path, PathBuf};
+
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
 use sdl2::render::{Texture, TextureCreator};
 use sdl2::surface::Surface;
 use sdl2::video::WindowContext;
 
+use tracing::{debug, warn};
+
 use crate::errors::prompt_err_and_panic;
 use crate::infraglobals;
+use crate::packer::PADDING;
+use crate::preload::DecodedImage;
+use crate::vfs;
+
+const TRATEX: &str = "hg::texture";
 
 pub struct TextureStore<'a> {
   textures: Vec<Option<Texture<'a>>>,  // The main texture holder
-  filenames: Vec<String>, // In their json declaration order
+  filenames: Vec<String>, // In their json declaration order. Empty : TTF text, cannot be reloaded
+  sizes: Vec<usize>, // Bytes of the loaded textures (RGBA), 0 if not loaded
+  last_used: Vec<u64>, // For the LRU eviction
+  clock: u64,
+  used_bytes: usize,
+  budget: Option<usize>, // Bytes. None : no limit
   texture_creator: &'a TextureCreator<WindowContext> // SDL2 thing to load textures
 }
 
@@ -17,44 +33,57 @@ impl<'a> TextureStore<'a> {
     TextureStore { 
       textures: Vec::with_capacity(initial_texture_count), 
       filenames: Vec::new(), 
+      sizes: Vec::new(),
+      last_used: Vec::new(),
+      clock: 0,
+      used_bytes: 0,
+      budget: None,
       texture_creator 
     }
   }
 
   pub fn get_texture(&mut self, texture_id: usize) -> &Texture {
-    if self.textures[texture_id].is_none() {
-      use sdl2::surface::Surface;
-      use sdl2::image::LoadSurface;
+    self.get_texture_mut(texture_id)
+  }
 
+  // To change the alpha/color modulation or the blend mode
+  pub fn get_texture_mut(&mut self, texture_id: usize) -> &mut Texture<'a> {
+    self.clock += 1;
+    self.last_used[texture_id] = self.clock;
+    if self.textures[texture_id].is_none() {
+      if self.filenames[texture_id].is_empty() {
+        prompt_err_and_panic("get_texture failed", &format!("the text texture {} was freed", texture_id), None);
+      }
       let img_path = infraglobals::get_img_path().join(&self.filenames[texture_id]);
-      let s = Surface::from_file(img_path) // TODO use sdl_img shortcut (file --> texture directly)
+      let s = vfs::load_surface(&img_path) // TODO use sdl_img shortcut (file --> texture directly)
         .unwrap_or_else(|err| { prompt_err_and_panic("img_load_color_key failed", &err, None); });
         
       let tex = s.as_texture(self.texture_creator)
         .unwrap_or_else(|err| { 
           prompt_err_and_panic("img_load_color_key(as_texture) failed", &err.to_string(), None); });
       
-      self.textures[texture_id] = Some(tex);
+      self.loaded(texture_id, tex);
     }
 
-    self.textures[texture_id].as_ref().unwrap()
+    self.textures[texture_id].as_mut().unwrap()
   }
 
   // Ensures the size of filenames remains the same than textures.
   pub fn push_new_texture(&mut self, path: String, opt_surface: Option<Surface>) -> usize {
+    self.textures.push(None); // In most cases texture is created lazily
+    self.filenames.push(path);
+    self.sizes.push(0);
+    self.last_used.push(0);
+    let texture_id = self.textures.len() - 1;
+
     if let Some(surface) = opt_surface {
-      if !path.is_empty() {
+      if !self.filenames[texture_id].is_empty() {
         panic!("Direct texture creation from surface is only supported for TTF");
       } // cause it wouldnt be lazy texture creation.... 
       let texture = self.texture_creator.create_texture_from_surface(&surface).unwrap();
-      self.textures.push(Some(texture));
-    }
-    else {
-      self.textures.push(None); // In most cases texture is created lazily
+      self.loaded(texture_id, texture);
     }
-
-    self.filenames.push(path);
-    return self.textures.len() - 1;
+    texture_id
   }
 
   pub fn set_alpha(&mut self, texture_id: usize, alpha: u8) {
@@ -65,6 +94,143 @@ impl<'a> TextureStore<'a> {
     self.textures[texture_id].as_mut().unwrap().set_blend_mode(sdl2::render::BlendMode::Blend);
     self.textures[texture_id].as_mut().unwrap().set_alpha_mod(alpha);
   }
+
+  // Frees the texture. A sheet will be reloaded on its next use, a TTF text is gone for good.
+  // Returns false if it was not loaded.
+  pub fn unload(&mut self, texture_id: usize) -> bool {
+    // Dropping it destroys the SDL texture
+    if self.textures[texture_id].take().is_none() {
+      return false;
+    }
+    self.used_bytes -= self.sizes[texture_id];
+    self.sizes[texture_id] = 0;
+    debug!(target: TRATEX, "Unloaded texture {} ({})", texture_id, self.filenames[texture_id]);
+    true
+  }
+
+  // Uploads an image decoded by a preload worker, unless it was loaded in the meantime
+  pub(crate) fn insert_decoded(&mut self, texture_id: usize, image: DecodedImage) {
+    if self.textures[texture_id].is_some() {
+      return;
+    }
+    let mut tex = match self.texture_creator.create_texture_static(PixelFormatEnum::ABGR8888, image.width, image.height) {
+      Ok(tex) => tex,
+      Err(e) => {
+        warn!(target: TRATEX, "Cannot create the preloaded texture {}: {}", texture_id, e);
+        return;
+      }
+    };
+    if let Err(e) = tex.update(None, &image.pixels, image.pitch) {
+      warn!(target: TRATEX, "Cannot upload the preloaded texture {}: {}", texture_id, e);
+      return;
+    }
+    tex.set_blend_mode(sdl2::render::BlendMode::Blend);
+    self.clock += 1;
+    self.last_used[texture_id] = self.clock; // Not the first one to evict
+    self.loaded(texture_id, tex);
+  }
+
+  // The sheets to preload (all of them if files is empty) : (texture id, path) of the ones not loaded yet,
+  // and the count of the loaded ones
+  pub(crate) fn preload_list(&self, files: &[String]) -> (Vec<(usize, PathBuf)>, usize) {
+    let ids: Vec<usize> = if files.is_empty() {
+      (0..self.textures.len()).filter(|&id| !self.filenames[id].is_empty()).collect()
+    } else {
+      files.iter().filter_map(|f| {
+        let id = self.id_of_file(f);
+        if id.is_none() {
+          warn!(target: TRATEX, "Preload: unknown sheet {}", f);
+        }
+        id
+      }).collect()
+    };
+    let (loaded, to_load): (Vec<usize>, Vec<usize>) = ids.into_iter().partition(|&id| self.textures[id].is_some());
+    let img_path = infraglobals::get_img_path();
+    (to_load.into_iter().map(|id| (id, img_path.join(&self.filenames[id]))).collect(), loaded.len())
+  }
+
+  // A blank page of a runtime atlas (cf. packer.rs). Like a TTF text : not reloadable, never evicted.
+  pub(crate) fn push_atlas_page(&mut self, size: u32) -> Option<usize> {
+    let mut tex = self.texture_creator.create_texture_static(PixelFormatEnum::ABGR8888, size, size)
+      .map_err(|e| warn!(target: TRATEX, "Cannot create an atlas page: {}", e)).ok()?;
+    let blank = vec![0u8; size as usize * size as usize * 4];
+    tex.update(None, &blank, size as usize * 4)
+      .map_err(|e| warn!(target: TRATEX, "Cannot clear an atlas page: {}", e)).ok()?;
+    tex.set_blend_mode(sdl2::render::BlendMode::Blend);
+    let texture_id = self.push_new_texture(String::new(), None);
+    self.loaded(texture_id, tex);
+    debug!(target: TRATEX, "Atlas page {} ({}x{})", texture_id, size, size);
+    Some(texture_id)
+  }
+
+  // Copies the surface into a part of an atlas page. The padding around it is cleared first : 
+  // a reused page still holds the former surfaces.
+  pub(crate) fn blit(&mut self, texture_id: usize, rect: Rect, surface: &Surface) -> Result<(), String> {
+    let rgba = surface.convert_format(PixelFormatEnum::ABGR8888)?;
+    let pixels = rgba.without_lock().ok_or("Surface must be locked")?;
+    let tex = self.textures[texture_id].as_mut().ok_or("Atlas page not loaded")?;
+    let query = tex.query();
+    let padded = Rect::new(rect.x(), rect.y(), 
+      (rect.width() + PADDING).min(query.width - rect.x() as u32), (rect.height() + PADDING).min(query.height - rect.y() as u32));
+    let blank = vec![0u8; padded.width() as usize * padded.height() as usize * 4];
+    tex.update(padded, &blank, padded.width() as usize * 4).map_err(|e| e.to_string())?;
+    tex.update(rect, pixels, rgba.pitch() as usize).map_err(|e| e.to_string())
+  }
+
+  // Hot reloading : a path relative to the img folder
+  pub(crate) fn id_of_path(&self, file: &Path) -> Option<usize> {
+    self.filenames.iter().position(|f| !f.is_empty() && Path::new(f) == file)
+  }
+
+  // Hot reloading : a TTF text rendered again or a sheet decoded again, in the same slot
+  // The old texture is kept if the new one cannot be created.
+  pub(crate) fn replace(&mut self, texture_id: usize, surface: Surface) {
+    match self.texture_creator.create_texture_from_surface(&surface) {
+      Ok(texture) => {
+        self.unload(texture_id);
+        self.loaded(texture_id, texture);
+      },
+      Err(e) => warn!(target: TRATEX, "Cannot replace the texture {}: {}", texture_id, e)
+    }
+  }
+
+  pub fn id_of_file(&self, file: &str) -> Option<usize> {
+    self.filenames.iter().position(|f| !f.is_empty() && f == file)
+  }
+
+  // The sheets least recently used are unloaded to stay under it. The texts are never evicted.
+  pub fn set_budget(&mut self, budget: Option<usize>) {
+    self.budget = budget;
+    self.evict(None);
+  }
+
+  pub fn used_bytes(&self) -> usize {
+    self.used_bytes
+  }
+
+  fn loaded(&mut self, texture_id: usize, tex: Texture<'a>) {
+    let query = tex.query();
+    self.sizes[texture_id] = query.width as usize * query.height as usize * 4;
+    self.used_bytes += self.sizes[texture_id];
+    self.textures[texture_id] = Some(tex);
+    self.evict(Some(texture_id));
+  }
+
+  fn evict(&mut self, keep: Option<usize>) {
+    let Some(budget) = self.budget else { return; };
+    while self.used_bytes > budget {
+      let victim = (0..self.textures.len())
+        .filter(|&id| Some(id) != keep && self.textures[id].is_some() && !self.filenames[id].is_empty())
+        .min_by_key(|&id| self.last_used[id]);
+      match victim {
+        Some(id) => { self.unload(id); },
+        None => {
+          warn!(target: TRATEX, "Texture budget exceeded: {} > {} bytes", self.used_bytes, budget);
+          return;
+        }
+      }
+    }
+  }
 }
 
 /////////////////////////////////////////////
@@ -81,7 +247,7 @@ mod tests {
   fn init_sdl2_context() -> TextureCreator<WindowContext> {
     infraglobals::setup_test_folder();
 
-    let (_sdl_ctx, _img_ctx, _ttf_ctx, _video, _mixer_ctx, canvas) 
+    let (_sdl_ctx, _img_ctx, _ttf_ctx, _video, _controller, _mixer_ctx, canvas) 
       = init_sdl2("HAMGRAPH TEST", 300, 400);
 
     canvas.texture_creator()
@@ -128,6 +294,24 @@ mod tests {
     }
   }
 
+  #[test]
+  fn test_texturemap_budget() {
+    let mut ctx = init_sdl2_context();
+    let mut texture_store = load_some_textures(&mut ctx);
+    texture_store.get_texture(0);
+    let one = texture_store.used_bytes();
+    texture_store.set_budget(Some(one));
+    texture_store.get_texture(2); // Same size : the first one goes
+    assert!(texture_store.textures[0].is_none());
+    assert!(texture_store.textures[2].is_some());
+    assert_eq!(texture_store.used_bytes(), one);
+
+    assert!(texture_store.unload(2));
+    assert!(!texture_store.unload(2));
+    assert_eq!(texture_store.used_bytes(), 0);
+    texture_store.get_texture(0); // Reloaded
+  }
+
   #[test]
   #[should_panic]
   fn test_texturemap_set_alpha_out_of_bounds() {
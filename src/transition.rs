@@ -0,0 +1,177 @@
+use sdl2::{pixels::{Color, PixelFormatEnum}, rect::Rect, render::{BlendMode, Texture}};
+use tracing::{debug, warn};
+
+use crate::{action_bus::ActionBus, scene::{SceneID, SceneStack}, Renderer};
+
+const TRATRANS: &str = "hg::transition";
+
+#[derive(Clone, Copy, Debug)]
+pub enum SlideDirection {
+  Left, // The new screen comes from the right and pushes the old one to the left
+  Right,
+  Up,
+  Down
+}
+
+// Durations are in seconds
+#[derive(Clone, Copy, Debug)]
+pub enum Transition {
+  Cut, // Instant switch, like CloseCurrentScene + Action::Scene
+  Fade { duration: f32 }, // Old screen fades to black, then the new one appears from black
+  Crossfade { duration: f32 },
+  Slide { duration: f32, direction: SlideDirection },
+}
+
+impl Transition {
+  pub fn duration(&self) -> f32 {
+    match self {
+      Transition::Cut => 0.0,
+      Transition::Fade { duration }
+      | Transition::Crossfade { duration }
+      | Transition::Slide { duration, .. } => *duration
+    }
+  }
+}
+
+// A running transition between two scene subtrees.
+// Both subtrees are rendered to their own target texture, then blended / slided on the screen.
+// NB: immediate (egui) UIs are not captured, they are still painted on top at the end of the frame.
+pub(crate) struct ActiveTransition<'a> {
+  kind: Transition,
+  pub(crate) outgoing: SceneID,
+  pub(crate) incoming: SceneID,
+  elapsed: f32,
+  out_tex: Option<Texture<'a>>,
+  in_tex: Option<Texture<'a>>,
+}
+
+impl<'a> ActiveTransition<'a> {
+  pub(crate) fn new(kind: Transition, outgoing: SceneID, incoming: SceneID) -> Self {
+    debug!(target: TRATRANS, "Transition {:?} from {} to {}", kind, outgoing, incoming);
+    Self { kind, outgoing, incoming, elapsed: 0.0, out_tex: None, in_tex: None }
+  }
+
+  // Returns true when the transition is over
+  pub(crate) fn advance(&mut self, delta_time: f32) -> bool {
+    self.elapsed += delta_time;
+    self.progress() >= 1.0
+  }
+
+  fn progress(&self) -> f32 {
+    let duration = self.kind.duration();
+    if duration <= 0.0 {
+      return 1.0;
+    }
+    (self.elapsed / duration).clamp(0.0, 1.0)
+  }
+
+  // Replaces SceneStack::render_all while the transition is running.
+  // Scenes that are not part of the transition are rendered as usual, the two screens are
+  // composited at the layer of the outgoing scene.
+  pub(crate) fn render(&mut self, scene_stack: &mut SceneStack, renderer: &mut Renderer<'a>, action_bus: &mut ActionBus) {
+    let (w, h) = renderer.canvas.output_size().unwrap_or((1, 1));
+    Self::ensure_target(&mut self.out_tex, renderer, w, h);
+    Self::ensure_target(&mut self.in_tex, renderer, w, h);
+
+    let out_ids = scene_stack.subtree(self.outgoing);
+    let in_ids = scene_stack.subtree(self.incoming);
+    let layer = scene_stack.layer_of(self.outgoing).unwrap_or(0);
+
+    // 1. Render both screens offscreen
+    for (tex, ids) in [(&self.out_tex, &out_ids), (&self.in_tex, &in_ids)] {
+      renderer.set_render_target(tex.as_ref());
+      renderer.canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+      renderer.canvas.clear();
+      scene_stack.render_some(renderer, action_bus, 0..usize::MAX, |id| ids.contains(&id));
+    }
+    renderer.set_render_target(None);
+
+    // 2. Render the other scenes, the transition being inserted at its layer
+    let others = |id: SceneID| !out_ids.contains(&id) && !in_ids.contains(&id);
+    scene_stack.render_some(renderer, action_bus, 0..layer + 1, others);
+    self.composite(renderer, w, h);
+    scene_stack.render_some(renderer, action_bus, layer + 1..usize::MAX, others);
+  }
+
+  fn ensure_target(tex: &mut Option<Texture<'a>>, renderer: &Renderer<'a>, w: u32, h: u32) {
+    if tex.as_ref().is_some_and(|t| t.query().width == w && t.query().height == h) {
+      return;
+    }
+    match renderer.texture_creator.create_texture_target(PixelFormatEnum::ARGB8888, w, h) {
+      Ok(mut t) => {
+        t.set_blend_mode(BlendMode::Blend);
+        *tex = Some(t);
+      },
+      Err(e) => {
+        warn!(target: TRATRANS, "Cannot create the transition target texture: {}", e);
+        *tex = None;
+      }
+    }
+  }
+
+  fn composite(&mut self, renderer: &mut Renderer, w: u32, h: u32) {
+    let p = self.progress();
+    let (Some(out_tex), Some(in_tex)) = (self.out_tex.as_mut(), self.in_tex.as_mut()) else {
+      return; // Textures could not be created, the screens just won't be displayed until the end
+    };
+    let full = Rect::new(0, 0, w, h);
+    match self.kind {
+      Transition::Cut => {
+        let _ = renderer.canvas.copy(in_tex, None, full);
+      },
+      Transition::Fade { .. } => {
+        // First half : old screen to black. Second half : black to new screen.
+        let (tex, black) = if p < 0.5 { (out_tex, p * 2.0) } else { (in_tex, (1.0 - p) * 2.0) };
+        tex.set_alpha_mod(255);
+        let _ = renderer.canvas.copy(tex, None, full);
+        renderer.canvas.set_blend_mode(BlendMode::Blend);
+        renderer.canvas.set_draw_color(Color::RGBA(0, 0, 0, (black * 255.0) as u8));
+        let _ = renderer.canvas.fill_rect(full);
+        renderer.canvas.set_blend_mode(BlendMode::None);
+      },
+      Transition::Crossfade { .. } => {
+        out_tex.set_alpha_mod(((1.0 - p) * 255.0) as u8);
+        in_tex.set_alpha_mod((p * 255.0) as u8);
+        let _ = renderer.canvas.copy(out_tex, None, full);
+        let _ = renderer.canvas.copy(in_tex, None, full);
+      },
+      Transition::Slide { direction, .. } => {
+        let p = p * p * (3.0 - 2.0 * p); // smoothstep
+        let (dx, dy) = match direction {
+          SlideDirection::Left => (-(w as f32), 0.0),
+          SlideDirection::Right => (w as f32, 0.0),
+          SlideDirection::Up => (0.0, -(h as f32)),
+          SlideDirection::Down => (0.0, h as f32),
+        };
+        out_tex.set_alpha_mod(255);
+        in_tex.set_alpha_mod(255);
+        let out_rect = Rect::new((dx * p) as i32, (dy * p) as i32, w, h);
+        let in_rect = Rect::new((dx * (p - 1.0)) as i32, (dy * (p - 1.0)) as i32, w, h);
+        let _ = renderer.canvas.copy(out_tex, None, out_rect);
+        let _ = renderer.canvas.copy(in_tex, None, in_rect);
+      }
+    }
+  }
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_transition_advance() {
+    let mut tr = ActiveTransition::new(Transition::Crossfade { duration: 0.5 }, 2, 3);
+    assert_eq!(tr.progress(), 0.0);
+    assert!(!tr.advance(0.25));
+    assert_eq!(tr.progress(), 0.5);
+    assert!(tr.advance(0.3));
+    assert_eq!(tr.progress(), 1.0); // Clamped
+
+    // A zero duration transition is over right away
+    let mut tr = ActiveTransition::new(Transition::Fade { duration: 0.0 }, 2, 3);
+    assert!(tr.advance(0.0));
+  }
+}
@@ -0,0 +1,346 @@
+// Asset reading : from the mounted archive (made by the hgpack tool) if it holds the file, else from the
+// loose files of the install path. Every asset loader of the engine goes through it.
+use std::{collections::HashMap, fs::{self, File}, io::{self, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}, sync::{Mutex, OnceLock}};
+
+use sdl2::{image::{ImageRWops, LoadSurface}, rwops::RWops, surface::Surface};
+use tracing::{debug, info};
+
+use crate::infraglobals;
+
+const TRAVFS: &str = "hg::vfs";
+
+// Looked for in the install path at the start
+pub const DEFAULT_ARCHIVE: &str = "assets.hgpack";
+
+const MAGIC: &[u8; 4] = b"HGPK";
+const VERSION: u32 = 1;
+const FLAG_COMPRESSED: u8 = 1; // zlib
+
+static ARCHIVE: OnceLock<Archive> = OnceLock::new();
+// The archived files SDL streams from (fonts, musics) : read once, kept for the whole run
+static STATIC_FILES: Mutex<Option<HashMap<String, &'static [u8]>>> = Mutex::new(None);
+
+struct Entry {
+  offset: u64,
+  size: u64, // Stored
+  original_size: u64,
+  flags: u8,
+}
+
+// The file layout (little endian) : "HGPK", version u32, entry count u32, then for each entry
+// name length u16, name (utf8, "img/hero.png"), offset u64, stored size u64, original size u64, flags u8,
+// then the data.
+pub struct Archive {
+  path: PathBuf,
+  entries: HashMap<String, Entry>,
+}
+
+impl Archive {
+  pub fn open(path: &Path) -> io::Result<Self> {
+    let mut file = io::BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(invalid("not a hgpack archive"));
+    }
+    if read_u32(&mut file)? != VERSION {
+      return Err(invalid("unsupported hgpack version"));
+    }
+    let count = read_u32(&mut file)?;
+    let mut entries = HashMap::with_capacity(count as usize);
+    for _ in 0..count {
+      let mut len = [0u8; 2];
+      file.read_exact(&mut len)?;
+      let mut name = vec![0u8; u16::from_le_bytes(len) as usize];
+      file.read_exact(&mut name)?;
+      let name = String::from_utf8(name).map_err(|_| invalid("bad entry name"))?;
+      let entry = Entry { offset: read_u64(&mut file)?, size: read_u64(&mut file)?, original_size: read_u64(&mut file)?, flags: {
+        let mut f = [0u8; 1];
+        file.read_exact(&mut f)?;
+        f[0]
+      }};
+      entries.insert(name, entry);
+    }
+    Ok(Self { path: path.to_path_buf(), entries })
+  }
+
+  pub fn contains(&self, name: &str) -> bool {
+    self.entries.contains_key(name)
+  }
+
+  // Sorted
+  pub fn names(&self) -> Vec<&str> {
+    let mut names: Vec<&str> = self.entries.keys().map(String::as_str).collect();
+    names.sort();
+    names
+  }
+
+  // (stored size, original size)
+  pub fn sizes(&self, name: &str) -> Option<(u64, u64)> {
+    self.entries.get(name).map(|e| (e.size, e.original_size))
+  }
+
+  pub fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+    let entry = self.entries.get(name).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name.to_owned()))?;
+    // A file handle per read : the preload worker reads too
+    let mut file = File::open(&self.path)?;
+    file.seek(SeekFrom::Start(entry.offset))?;
+    let mut data = vec![0u8; entry.size as usize];
+    file.read_exact(&mut data)?;
+    if entry.flags & FLAG_COMPRESSED != 0 {
+      data = decompress(&data, entry.original_size)?;
+    }
+    Ok(data)
+  }
+}
+
+// The output may not exist yet : its folder then
+fn canonical(path: &Path) -> PathBuf {
+  fs::canonicalize(path).ok()
+    .or_else(|| {
+      let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+      Some(fs::canonicalize(parent).ok()?.join(path.file_name()?))
+    })
+    .unwrap_or_else(|| path.to_path_buf())
+}
+
+// Packs every file under dir (the install folder). compress : zlib, for the entries it makes smaller.
+// Returns the number of entries.
+pub fn pack_dir(dir: &Path, out: &Path, compress: bool) -> io::Result<usize> {
+  let mut files = Vec::new();
+  collect_files(dir, &mut files)?;
+  files.sort();
+
+  // Packing into the install folder itself : not the output, nor the archives packed before
+  let out = canonical(out);
+  let mut names = Vec::new();
+  let mut blobs = Vec::new();
+  for path in &files {
+    if canonical(path) == out || path.file_name().is_some_and(|f| f == DEFAULT_ARCHIVE) {
+      continue;
+    }
+    let name = archive_name(path.strip_prefix(dir).unwrap_or(path)).ok_or_else(|| invalid("non utf8 file name"))?;
+    let data = fs::read(path)?;
+    if data.starts_with(MAGIC) {
+      debug!(target: TRAVFS, "Not packing the archive {}", name);
+      continue;
+    }
+    let original_size = data.len() as u64;
+    let (data, flags) = match compress.then(|| compress_data(&data)).transpose()? {
+      Some(packed) if packed.len() < data.len() => (packed, FLAG_COMPRESSED),
+      _ => (data, 0)
+    };
+    debug!(target: TRAVFS, "Packing {} ({} -> {} bytes)", name, original_size, data.len());
+    names.push((name, original_size, flags));
+    blobs.push(data);
+  }
+
+  let index_len: usize = names.iter().map(|(name, ..)| 2 + name.len() + 8 * 3 + 1).sum();
+  let mut offset = (4 + 4 + 4 + index_len) as u64;
+  let mut w = io::BufWriter::new(File::create(out)?);
+  w.write_all(MAGIC)?;
+  w.write_all(&VERSION.to_le_bytes())?;
+  w.write_all(&(names.len() as u32).to_le_bytes())?;
+  for ((name, original_size, flags), blob) in names.iter().zip(&blobs) {
+    w.write_all(&(name.len() as u16).to_le_bytes())?;
+    w.write_all(name.as_bytes())?;
+    w.write_all(&offset.to_le_bytes())?;
+    w.write_all(&(blob.len() as u64).to_le_bytes())?;
+    w.write_all(&original_size.to_le_bytes())?;
+    w.write_all(&[*flags])?;
+    offset += blob.len() as u64;
+  }
+  for blob in &blobs {
+    w.write_all(blob)?;
+  }
+  w.flush()?;
+  Ok(names.len())
+}
+
+// The archive replaces the loose files it holds. Only one archive, mounted before HamGraph::new.
+pub fn mount(path: &Path) -> io::Result<()> {
+  let archive = Archive::open(path)?;
+  info!(target: TRAVFS, "Mounted {:?} ({} files)", path, archive.entries.len());
+  ARCHIVE.set(archive).map_err(|_| io::Error::new(io::ErrorKind::AlreadyExists, "an archive is already mounted"))
+}
+
+// <install path>/assets.hgpack, if there is one
+pub(crate) fn mount_default() {
+  let path = infraglobals::get_install_path().join(DEFAULT_ARCHIVE);
+  if ARCHIVE.get().is_none() && path.is_file() && let Err(e) = mount(&path) {
+    tracing::warn!(target: TRAVFS, "Cannot mount {:?}: {}", path, e);
+  }
+}
+
+// path : an asset path under the install path (e.g. get_img_path().join("hero.png"))
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+  match archived(path) {
+    Some((archive, name)) => archive.read(&name),
+    None => fs::read(path)
+  }
+}
+
+pub fn exists(path: &Path) -> bool {
+  archived(path).is_some() || path.exists()
+}
+
+pub(crate) fn in_archive(path: &Path) -> bool {
+  archived(path).is_some()
+}
+
+// The files of a folder (not recursive) : the archived ones and the loose ones
+pub fn list_dir(dir: &Path) -> Vec<PathBuf> {
+  let mut files: Vec<PathBuf> = fs::read_dir(dir).into_iter().flatten().flatten()
+    .map(|entry| entry.path()).filter(|p| p.is_file()).collect();
+  if let Some(archive) = ARCHIVE.get() && let Some(prefix) = install_name(dir) {
+    let prefix = if prefix.is_empty() { prefix } else { prefix + "/" };
+    for name in archive.entries.keys() {
+      if let Some(file) = name.strip_prefix(&prefix) && !file.contains('/') {
+        let path = dir.join(file);
+        if !files.contains(&path) {
+          files.push(path);
+        }
+      }
+    }
+  }
+  files.sort();
+  files
+}
+
+// For an archived file only (None otherwise)
+pub(crate) fn read_static(path: &Path) -> Option<io::Result<&'static [u8]>> {
+  let (archive, name) = archived(path)?;
+  let mut files = STATIC_FILES.lock().unwrap_or_else(|e| e.into_inner());
+  let files = files.get_or_insert_with(HashMap::new);
+  if let Some(bytes) = files.get(&name) {
+    return Some(Ok(bytes));
+  }
+  Some(archive.read(&name).map(|bytes| {
+    let bytes: &'static [u8] = Box::leak(bytes.into_boxed_slice());
+    files.insert(name, bytes);
+    bytes
+  }))
+}
+
+pub(crate) fn load_surface(path: &Path) -> Result<Surface<'static>, String> {
+  if !in_archive(path) {
+    return Surface::from_file(path);
+  }
+  let bytes = read(path).map_err(|e| format!("{:?}: {}", path, e))?;
+  RWops::from_bytes(&bytes)?.load() // SDL_image copies the pixels
+}
+
+fn archived(path: &Path) -> Option<(&'static Archive, String)> {
+  let archive = ARCHIVE.get()?;
+  let name = install_name(path)?;
+  archive.contains(&name).then_some((archive, name))
+}
+
+// The archive name of a path under the install path
+fn install_name(path: &Path) -> Option<String> {
+  archive_name(path.strip_prefix(infraglobals::get_install_path()).ok()?)
+}
+
+// "img/chars/hero.png", whatever the OS
+fn archive_name(rel: &Path) -> Option<String> {
+  let parts: Option<Vec<&str>> = rel.components().map(|c| c.as_os_str().to_str()).collect();
+  Some(parts?.join("/"))
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      collect_files(&path, files)?;
+    } else {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(feature = "compression")]
+fn compress_data(data: &[u8]) -> io::Result<Vec<u8>> {
+  let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+  encoder.write_all(data)?;
+  encoder.finish()
+}
+
+#[cfg(not(feature = "compression"))]
+fn compress_data(_data: &[u8]) -> io::Result<Vec<u8>> {
+  Err(io::Error::new(io::ErrorKind::Unsupported, "hamgraph built without the compression feature"))
+}
+
+#[cfg(feature = "compression")]
+fn decompress(data: &[u8], original_size: u64) -> io::Result<Vec<u8>> {
+  let mut out = Vec::with_capacity(original_size as usize);
+  flate2::read::ZlibDecoder::new(data).read_to_end(&mut out)?;
+  Ok(out)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_data: &[u8], _original_size: u64) -> io::Result<Vec<u8>> {
+  Err(io::Error::new(io::ErrorKind::Unsupported, "hamgraph built without the compression feature"))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+  let mut b = [0u8; 4];
+  r.read_exact(&mut b)?;
+  Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+  let mut b = [0u8; 8];
+  r.read_exact(&mut b)?;
+  Ok(u64::from_le_bytes(b))
+}
+
+fn invalid(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+
+////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_pack_and_read() {
+    let root = infraglobals::test_temp_dir("vfs");
+    let install = root.join("install");
+    fs::create_dir_all(install.join("img").join("chars")).unwrap();
+    fs::create_dir_all(install.join("conf")).unwrap();
+    fs::write(install.join("img").join("chars").join("hero.png"), b"not really a png").unwrap();
+    let desc = format!("[{}]", " ".repeat(1000));
+    fs::write(install.join("conf").join("spritedesc.json"), &desc).unwrap();
+
+    let out = root.join("assets.hgpack");
+    assert_eq!(pack_dir(&install, &out, cfg!(feature = "compression")).unwrap(), 2);
+    let archive = Archive::open(&out).unwrap();
+    assert_eq!(archive.names(), vec!["conf/spritedesc.json", "img/chars/hero.png"]);
+    assert_eq!(archive.read("img/chars/hero.png").unwrap(), b"not really a png");
+    assert_eq!(archive.read("conf/spritedesc.json").unwrap(), desc.as_bytes());
+    if cfg!(feature = "compression") {
+      let (stored, original) = archive.sizes("conf/spritedesc.json").unwrap();
+      assert!(stored < original);
+    }
+    assert!(archive.read("nope").is_err());
+
+    // Into the install folder : neither the output nor the former archives are packed
+    fs::write(install.join("old.bin"), b"HGPK whatever").unwrap();
+    assert_eq!(pack_dir(&install, &install.join(DEFAULT_ARCHIVE), false).unwrap(), 2);
+    assert_eq!(pack_dir(&install, &install.join("conf").join("..").join("other.hgpack"), false).unwrap(), 2);
+
+    fs::write(&out, b"HGPX").unwrap();
+    assert!(Archive::open(&out).is_err());
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_archive_name() {
+    assert_eq!(archive_name(&Path::new("img").join("chars").join("hero.png")).as_deref(), Some("img/chars/hero.png"));
+    assert_eq!(archive_name(Path::new("")).as_deref(), Some(""));
+  }
+}